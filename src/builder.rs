@@ -0,0 +1,2613 @@
+use crate::error::{Error, FileStatus};
+use crate::limits::{
+    effective_limits, is_valid_result, is_within_limits, scale_value, scaled_units, test_header,
+    tester_pass_fail, PtrOptionalData, TestHeader, TestLimits,
+};
+use crate::options::{Options, RetestPolicy, TestOrder};
+use crate::pins::{pin_name, PinMap};
+use crate::reader::open_stdf;
+use crate::report::{FileReport, Report};
+use crate::scan::{ScanFailRows, ScanMap};
+use crate::summary::*;
+use crate::{
+    BinDescription, BinNum, ColumnName, FileName, FunctionalResult, HeadNum, PartId, PinIndex,
+    SiteNum, TestResult,
+};
+use chrono::{TimeZone, Utc};
+use polars::prelude::*;
+use rust_stdf::StdfRecord;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+struct Msg {
+    sender: String,
+    rec: Result<StdfRecord, Error>,
+}
+
+// test columns with their test numbers, in the order the tests were executed
+#[derive(Debug, Default)]
+struct ColumnOrder {
+    columns: Vec<(u32, ColumnName)>,
+    seen: HashSet<ColumnName>,
+}
+
+impl ColumnOrder {
+    // insert columns not seen before after the column which preceded
+    // them in `sequence`, so conditional tests keep their place in the flow,
+    // before the first column seen before when none preceded them, and
+    // at the end when `sequence` has no column seen before
+    fn merge(&mut self, sequence: &[(u32, ColumnName)]) {
+        if sequence.iter().all(|(_, tname)| self.seen.contains(tname)) {
+            return;
+        }
+
+        let mut next: Option<usize> = None;
+        let mut leading: Vec<(u32, ColumnName)> = vec![];
+        for (test_num, tname) in sequence {
+            if self.seen.contains(tname) {
+                let position = self.columns.iter().position(|x| &x.1 == tname).unwrap();
+                let n_leading = leading.len();
+                self.columns.splice(position..position, leading.drain(..));
+                next = Some(position + n_leading + 1);
+            } else {
+                self.seen.insert(tname.clone());
+                match next.as_mut() {
+                    Some(next) => {
+                        self.columns.insert(*next, (*test_num, tname.clone()));
+                        *next += 1;
+                    }
+                    None => leading.push((*test_num, tname.clone())),
+                }
+            }
+        }
+        self.columns.extend(leading);
+    }
+
+    fn ordered(&self, test_order: TestOrder) -> Vec<(u32, ColumnName)> {
+        let mut columns = self.columns.clone();
+        if test_order == TestOrder::TestNumber {
+            columns.sort_by_key(|(test_num, _)| *test_num);
+        }
+        columns
+    }
+}
+
+// rows of the long format report, one per part and test
+#[derive(Debug, Default)]
+struct LongRows {
+    part_index: Vec<usize>,
+    part_id: Vec<String>,
+    head_num: Vec<u32>,
+    site_num: Vec<u32>,
+    test_num: Vec<u32>,
+    test_txt: Vec<String>,
+    result: Vec<TestResult>,
+    lo_limit: Vec<Option<f32>>,
+    hi_limit: Vec<Option<f32>>,
+    units: Vec<Option<String>>,
+    pass_fail: Vec<Option<u32>>,
+}
+
+impl LongRows {
+    fn push(
+        &mut self,
+        part_index: usize,
+        prr: &rust_stdf::PRR,
+        header: TestHeader,
+        result: TestResult,
+        pass: Option<bool>,
+    ) {
+        self.part_index.push(part_index);
+        self.part_id.push(prr.part_id.clone());
+        self.head_num.push(prr.head_num as u32);
+        self.site_num.push(prr.site_num as u32);
+        self.test_num.push(header.test_num);
+        self.test_txt.push(header.test_txt);
+        self.result.push(result);
+        self.lo_limit.push(header.lo_limit);
+        self.hi_limit.push(header.hi_limit);
+        self.units.push(header.units);
+        self.pass_fail.push(pass.map(|x| x as u32));
+    }
+
+    fn into_df(
+        self,
+        file_name: &str,
+        retests: &[(usize, u32)],
+        keep: &[bool],
+    ) -> PolarsResult<DataFrame> {
+        let mask: BooleanChunked = self.part_index.iter().map(|i| keep[*i]).collect();
+        let retest_index: Vec<u32> = self.part_index.iter().map(|i| retests[*i].1).collect();
+
+        DataFrame::new(vec![
+            Series::new("File Name", vec![file_name; self.part_id.len()]),
+            Series::new("Part ID", self.part_id),
+            Series::new("Retest Index", retest_index),
+            Series::new("Head", self.head_num),
+            Series::new("Site", self.site_num),
+            Series::new("Test Num", self.test_num),
+            Series::new("Test Name", self.test_txt),
+            Series::new("Result", self.result),
+            Series::new("Lo Limit", self.lo_limit),
+            Series::new("Hi Limit", self.hi_limit),
+            Series::new("Units", self.units),
+            Series::new("Pass/Fail", self.pass_fail),
+        ])?
+        .filter(&mask)
+    }
+}
+
+// one row per failing FTR, with the vector and pins of the failure
+#[derive(Default)]
+struct FtrFailRows {
+    part_index: Vec<usize>,
+    part_id: Vec<String>,
+    head_num: Vec<u32>,
+    site_num: Vec<u32>,
+    test_num: Vec<u32>,
+    test_txt: Vec<String>,
+    vect_nam: Vec<String>,
+    cycl_cnt: Vec<Option<u32>>,
+    rel_vadr: Vec<Option<u32>>,
+    rept_cnt: Vec<Option<u32>>,
+    num_fail: Vec<Option<u32>>,
+    xfail_ad: Vec<Option<i32>>,
+    yfail_ad: Vec<Option<i32>>,
+    vect_off: Vec<Option<i32>>,
+    pins: Vec<String>,
+    pin_states: Vec<String>,
+}
+
+impl FtrFailRows {
+    fn push(
+        &mut self,
+        part_index: usize,
+        prr: &rust_stdf::PRR,
+        ftr: &rust_stdf::FTR,
+        pin_map: Option<&PinMap>,
+    ) {
+        // bit 0 set = CYCL_CNT invalid, bit 1 = REL_VADR, bit 2 = REPT_CNT,
+        // bit 3 = NUM_FAIL, bit 4 = XFAIL_AD and YFAIL_AD, bit 5 = VECT_OFF
+        let is_valid = |bit: u8| ftr.opt_flag[0] & (1 << bit) == 0;
+
+        self.part_index.push(part_index);
+        self.part_id.push(prr.part_id.clone());
+        self.head_num.push(prr.head_num as u32);
+        self.site_num.push(prr.site_num as u32);
+        self.test_num.push(ftr.test_num);
+        self.test_txt.push(ftr.test_txt.clone());
+        self.vect_nam.push(ftr.vect_nam.clone());
+        self.cycl_cnt
+            .push(Some(ftr.cycl_cnt).filter(|_| is_valid(0)));
+        self.rel_vadr
+            .push(Some(ftr.rel_vadr).filter(|_| is_valid(1)));
+        self.rept_cnt
+            .push(Some(ftr.rept_cnt).filter(|_| is_valid(2)));
+        self.num_fail
+            .push(Some(ftr.num_fail).filter(|_| is_valid(3)));
+        self.xfail_ad
+            .push(Some(ftr.xfail_ad).filter(|_| is_valid(4)));
+        self.yfail_ad
+            .push(Some(ftr.yfail_ad).filter(|_| is_valid(4)));
+        self.vect_off
+            .push(Some(ftr.vect_off as i32).filter(|_| is_valid(5)));
+
+        // pins are named through the PMR, paired with their returned states
+        let pins: Vec<String> = ftr.rtn_indx.iter().map(|x| pin_name(pin_map, *x)).collect();
+        let pin_states: Vec<String> = ftr.rtn_stat.iter().map(|x| x.to_string()).collect();
+        self.pins.push(pins.join(","));
+        self.pin_states.push(pin_states.join(","));
+    }
+
+    fn into_df(
+        self,
+        file_name: &str,
+        retests: &[(usize, u32)],
+        keep: &[bool],
+    ) -> PolarsResult<DataFrame> {
+        let mask: BooleanChunked = self.part_index.iter().map(|i| keep[*i]).collect();
+        let retest_index: Vec<u32> = self.part_index.iter().map(|i| retests[*i].1).collect();
+
+        DataFrame::new(vec![
+            Series::new("File Name", vec![file_name; self.part_id.len()]),
+            Series::new("Part ID", self.part_id),
+            Series::new("Retest Index", retest_index),
+            Series::new("Head", self.head_num),
+            Series::new("Site", self.site_num),
+            Series::new("Test Num", self.test_num),
+            Series::new("Test Name", self.test_txt),
+            Series::new("Vector Name", self.vect_nam),
+            Series::new("Cycle Count", self.cycl_cnt),
+            Series::new("Rel Vector Addr", self.rel_vadr),
+            Series::new("Repeat Count", self.rept_cnt),
+            Series::new("Num Fail Pins", self.num_fail),
+            Series::new("X Fail Addr", self.xfail_ad),
+            Series::new("Y Fail Addr", self.yfail_ad),
+            Series::new("Vector Offset", self.vect_off),
+            Series::new("Pins", self.pins),
+            Series::new("Pin States", self.pin_states),
+        ])?
+        .filter(&mask)
+    }
+}
+
+// group each retest with the part it retests using the PRR part flags,
+// returning the group and retest index (0 for the first test) of every part
+fn retest_groups<'a>(
+    parts: impl IntoIterator<Item = (&'a rust_stdf::PRR, &'a Option<String>)>,
+) -> Vec<(usize, u32)> {
+    let mut by_part_id: HashMap<(Option<&String>, &String), usize> = HashMap::new();
+    let mut by_xy: HashMap<(Option<&String>, i16, i16), usize> = HashMap::new();
+    let mut group_sizes: Vec<u32> = vec![];
+
+    parts
+        .into_iter()
+        .map(|(prr, wafer_id)| {
+            let part_key = (wafer_id.as_ref(), &prr.part_id);
+            let xy_key = (wafer_id.as_ref(), prr.x_coord, prr.y_coord);
+
+            // bit 0 set = retest of the part with the same PART_ID
+            // bit 1 set = retest of the part with the same X/Y coordinates
+            let retested = match prr.part_flg[0] & 0b0000_0011 {
+                0b01 => by_part_id.get(&part_key).copied(),
+                0b10 => by_xy.get(&xy_key).copied(),
+                _ => None,
+            };
+
+            let group = retested.unwrap_or_else(|| {
+                group_sizes.push(0);
+                group_sizes.len() - 1
+            });
+            let index = group_sizes[group];
+            group_sizes[group] += 1;
+
+            by_part_id.insert(part_key, group);
+            by_xy.insert(xy_key, group);
+
+            (group, index)
+        })
+        .collect()
+}
+
+// parts of each retest group of a file or lot
+type RetestGroups = Vec<(usize, u32)>;
+type GroupSizes = HashMap<usize, u32>;
+
+fn group_sizes(retests: &[(usize, u32)]) -> GroupSizes {
+    let mut sizes = GroupSizes::new();
+    for (group, _) in retests {
+        *sizes.entry(*group).or_default() += 1;
+    }
+    sizes
+}
+
+// parts whose results are reported under the retest policy
+fn kept_parts(retests: &[(usize, u32)], retest: RetestPolicy) -> Vec<bool> {
+    let group_sizes = group_sizes(retests);
+    retests
+        .iter()
+        .map(|(group, index)| match retest {
+            RetestPolicy::All => true,
+            RetestPolicy::First => *index == 0,
+            RetestPolicy::Last => *index + 1 == group_sizes[group],
+        })
+        .collect()
+}
+
+type LotBinKey = (String, &'static str, BinNum, HeadNum, SiteNum);
+
+// lots can span several files, so the bin counts of each file are added to its lot
+fn add_lot_bins(lot_bins: &mut HashMap<LotBinKey, BinRow>, rows: &[BinRow]) {
+    for row in rows {
+        lot_bins
+            .entry((
+                row.lot_id.clone(),
+                row.bin_type,
+                row.bin_num,
+                row.head_num,
+                row.site_num,
+            ))
+            .and_modify(|x| {
+                x.summary_cnt = match (x.summary_cnt, row.summary_cnt) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+                x.prr_cnt += row.prr_cnt;
+            })
+            .or_insert(row.clone());
+    }
+}
+
+// limits shared by every site are kept under `None`, the limits of
+// one head/site under `Some` when sites keep their own limits
+type LimitScope = Option<(HeadNum, SiteNum)>;
+
+// test records of the current part of each site, with the limits in force
+// when each record was added
+type SiteRecords<T> = HashMap<(HeadNum, SiteNum), Vec<(T, PtrOptionalData)>>;
+
+// the limits of a test and the indices of the parts which ran the test under them
+type LimitLog = Vec<(Vec<usize>, TestHeader)>;
+
+// bit 4/5 set = the record has no low/high limit of its own, and reuses
+// the default low/high limit along with its bit 6/7 no limit flag
+fn with_default_limits(data: &PtrOptionalData, defaults: &PtrOptionalData) -> PtrOptionalData {
+    let mut data = data.clone();
+    let (Some([mut opt_flag]), Some([default_flag])) = (data.opt_flag, defaults.opt_flag) else {
+        return data;
+    };
+
+    if opt_flag & 0b0001_0000 != 0 {
+        data.lo_limit = defaults.lo_limit;
+        data.llm_scal = defaults.llm_scal;
+        opt_flag = (opt_flag & !0b0101_0000) | (default_flag & 0b0101_0000);
+    }
+    if opt_flag & 0b0010_0000 != 0 {
+        data.hi_limit = defaults.hi_limit;
+        data.hlm_scal = defaults.hlm_scal;
+        opt_flag = (opt_flag & !0b1010_0000) | (default_flag & 0b1010_0000);
+    }
+    data.opt_flag = Some([opt_flag]);
+
+    data
+}
+
+// the first record of a test with optional data sets the default limits,
+// later records reuse them, or replace them when limits are dynamic
+fn set_default_limits(
+    limits: &mut TestLimits,
+    test_key: &ColumnName,
+    data: PtrOptionalData,
+    is_warned: bool,
+    is_dynamic: bool,
+) {
+    match limits.get_mut(test_key) {
+        Some(optional_data) if optional_data.opt_flag.is_none() => *optional_data = data,
+        Some(optional_data)
+            if is_dynamic
+                && data.opt_flag.is_some()
+                && effective_limits(&with_default_limits(&data, optional_data))
+                    != effective_limits(optional_data) =>
+        {
+            let data = with_default_limits(&data, optional_data);
+            optional_data.opt_flag = data.opt_flag;
+            optional_data.llm_scal = data.llm_scal;
+            optional_data.hlm_scal = data.hlm_scal;
+            optional_data.lo_limit = data.lo_limit;
+            optional_data.hi_limit = data.hi_limit;
+        }
+        Some(optional_data) => {
+            let data = with_default_limits(&data, optional_data);
+            let hi_lim_changed =
+                data.hi_limit.is_some() && (data.hi_limit != optional_data.hi_limit);
+            let lo_lim_changed =
+                data.lo_limit.is_some() && (data.lo_limit != optional_data.lo_limit);
+
+            if is_warned && (hi_lim_changed || lo_lim_changed) {
+                println!("attempt to update existing limits, using initial limit :: {} :: ({:?},{:?}) -> ({:?},{:?})",
+                    test_key,
+                    optional_data.lo_limit,
+                    optional_data.hi_limit,
+                    data.lo_limit,
+                    data.hi_limit,
+                );
+            }
+        }
+        None => {
+            limits.insert(test_key.clone(), data);
+        }
+    }
+}
+
+// the limits of a record are shared by every site, and also kept
+// for the site of the record when sites keep their own limits
+fn add_limits(
+    limits: &mut HashMap<LimitScope, TestLimits>,
+    site: (HeadNum, SiteNum),
+    test_key: &ColumnName,
+    data: PtrOptionalData,
+    options: &Options,
+) {
+    if options.per_site_limits {
+        // a site without limits of its own reuses the shared default limits
+        let has_site_limits = limits
+            .get(&Some(site))
+            .is_some_and(|x| x.contains_key(test_key));
+        let site_data = match limits.get(&None).and_then(|x| x.get(test_key)) {
+            Some(defaults) if !has_site_limits => with_default_limits(&data, defaults),
+            _ => data.clone(),
+        };
+        set_default_limits(
+            limits.entry(Some(site)).or_default(),
+            test_key,
+            site_data,
+            true,
+            options.dynamic_limits,
+        );
+    }
+    set_default_limits(
+        limits.entry(None).or_default(),
+        test_key,
+        data,
+        !options.per_site_limits,
+        options.dynamic_limits,
+    );
+}
+
+// record the part under the limits it ran the test with, starting a new
+// entry when they differ from the limits of the previous part
+fn log_limits(log: &mut LimitLog, part_index: usize, header: TestHeader) {
+    match log.last_mut() {
+        Some((parts, x)) if (x.lo_limit, x.hi_limit) == (header.lo_limit, header.hi_limit) => {
+            if parts.last() != Some(&part_index) {
+                parts.push(part_index);
+            }
+        }
+        _ => log.push((vec![part_index], header)),
+    }
+}
+
+// the limits of the site when it has its own, otherwise the shared limits
+fn site_limits<'a>(
+    limits: &'a mut HashMap<LimitScope, TestLimits>,
+    site: (HeadNum, SiteNum),
+    test_key: &ColumnName,
+) -> &'a PtrOptionalData {
+    let is_site_limits = limits
+        .get(&Some(site))
+        .and_then(|x| x.get(test_key))
+        .is_some_and(|x| x.opt_flag.is_some());
+    let scope = if is_site_limits { Some(site) } else { None };
+
+    limits
+        .entry(scope)
+        .or_default()
+        .entry(test_key.clone())
+        .or_insert(PtrOptionalData {
+            opt_flag: Some([0b1111_1111]), // all invalid
+            res_scal: None,
+            llm_scal: None,
+            hlm_scal: None,
+            lo_limit: None,
+            hi_limit: None,
+            units: None,
+            _c_resfmt: None,
+            _c_llmfmt: None,
+            _c_hlmfmt: None,
+            lo_spec: None,
+            hi_spec: None,
+        })
+}
+
+// the SDR whose site group covers this head/site, if any
+fn sdr_for_site(
+    sdrs: &[rust_stdf::SDR],
+    head_num: HeadNum,
+    site_num: SiteNum,
+) -> Option<&rust_stdf::SDR> {
+    sdrs.iter()
+        .find(|sdr| sdr.head_num == head_num && sdr.site_num.contains(&site_num))
+}
+
+/// Collates the records of STDF files into parametric, bin, yield and test reports
+#[derive(Default)]
+pub struct ParametricBuilder {
+    options: Options,
+    // files in the order they were added
+    files: Vec<FileName>,
+    record_counts: HashMap<FileName, usize>,
+    // error which stopped reading each file
+    read_errors: HashMap<FileName, Error>,
+    mir_cols: HashMap<FileName, rust_stdf::MIR>,
+    sdr_cols: HashMap<FileName, Vec<rust_stdf::SDR>>,
+    // V4-2007 version updates named by the VUR records of each file
+    vur_cols: HashMap<FileName, Vec<String>>,
+    // pir_cols: HashMap<FileName, rust_stdf::PIR>,
+    hbr_cols: HashMap<FileName, HashMap<BinNum, BinDescription>>,
+    sbr_cols: HashMap<FileName, HashMap<BinNum, BinDescription>>,
+    bin_records: HashMap<FileName, Vec<BinRecord>>,
+    limit_cols: HashMap<FileName, HashMap<LimitScope, TestLimits>>,
+    ptr_cols: HashMap<FileName, SiteRecords<rust_stdf::PTR>>,
+    mpr_cols: HashMap<FileName, SiteRecords<rust_stdf::MPR>>,
+    ftr_cols: HashMap<FileName, HashMap<(HeadNum, SiteNum), Vec<rust_stdf::FTR>>>,
+    str_cols: HashMap<FileName, HashMap<(HeadNum, SiteNum), Vec<rust_stdf::STR>>>,
+    pin_maps: HashMap<FileName, PinMap>,
+    // default RTN_INDX per MPR test, set by the first MPR with this test number
+    mpr_pins: HashMap<FileName, HashMap<ColumnName, Vec<PinIndex>>>,
+    ptr_data: HashMap<FileName, HashMap<ColumnName, Vec<TestResult>>>,
+    ftr_data: HashMap<FileName, HashMap<ColumnName, Vec<FunctionalResult>>>,
+    pf_data: HashMap<FileName, HashMap<ColumnName, Vec<FunctionalResult>>>,
+    unit_cols: HashMap<FileName, HashMap<ColumnName, String>>,
+    header_cols: HashMap<FileName, HashMap<ColumnName, TestHeader>>,
+    long_data: HashMap<FileName, LongRows>,
+    ftr_fail_data: HashMap<FileName, FtrFailRows>,
+    scan_maps: HashMap<FileName, ScanMap>,
+    scan_fail_data: HashMap<FileName, ScanFailRows>,
+    // limits in force of each test, from the part index they took effect
+    limit_log: HashMap<FileName, HashMap<(ColumnName, LimitScope), LimitLog>>,
+    n_parts_observered: HashMap<FileName, PartId>,
+    prrs: HashMap<FileName, Vec<rust_stdf::PRR>>,
+    // wafers currently being tested, per head
+    wir_cols: HashMap<FileName, HashMap<HeadNum, rust_stdf::WIR>>,
+    wafer_cols: HashMap<FileName, Vec<(Option<rust_stdf::WIR>, rust_stdf::WRR)>>,
+    wcr_cols: HashMap<FileName, rust_stdf::WCR>,
+    part_wafer_ids: HashMap<FileName, Vec<Option<String>>>,
+    tsr_cols: HashMap<FileName, Vec<rust_stdf::TSR>>,
+    // tests of the current part of each site, in the order they were executed
+    test_sequence: HashMap<FileName, HashMap<(HeadNum, SiteNum), Vec<ColumnName>>>,
+    column_orders: HashMap<FileName, ColumnOrder>,
+}
+
+impl ParametricBuilder {
+    pub fn new(options: Options) -> Self {
+        ParametricBuilder {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Read STDF files, one thread per file
+    pub fn add_files(&mut self, paths: &[String]) {
+        for path in paths {
+            if !self.files.contains(path) {
+                self.files.push(path.clone());
+            }
+        }
+
+        let (tx, rx) = mpsc::channel();
+
+        let mut handles: Vec<JoinHandle<()>> = vec![];
+
+        for stdf_path in paths {
+            let stdf_path = stdf_path.clone();
+            let tx_to_closure = tx.clone();
+            let handle = thread::spawn(move || {
+                let mut reader = match open_stdf(&stdf_path) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        let _ = tx_to_closure.send(Msg {
+                            sender: stdf_path,
+                            rec: Err(e),
+                        });
+                        return;
+                    }
+                };
+
+                // use type filter to work on certain types,
+                // use `|` to combine multiple typs
+                // let rec_types = REC_PIR | REC_PRR | REC_PTR;
+                // iterator starts from current file position,
+                // if file hits EOF, it will NOT redirect to 0.
+                for rec_result in reader.get_record_iter()
+                // .filter(|x| x.is_type(rec_types))
+                {
+                    // the rest of the file cannot be read after a malformed record
+                    let is_malformed = rec_result.is_err();
+                    let rec = rec_result
+                        .map_err(|e| Error::MalformedRecord(stdf_path.clone(), e.to_string()));
+
+                    if let Err(err) = tx_to_closure.send(Msg {
+                        sender: stdf_path.clone(),
+                        rec,
+                    }) {
+                        println!("Error sending message :: {:?}", err);
+                        break;
+                    }
+
+                    if is_malformed {
+                        break;
+                    }
+                }
+            });
+
+            handles.push(handle);
+        }
+
+        drop(tx);
+
+        for msg in rx {
+            match msg.rec {
+                Ok(rec) => self.add_record(&msg.sender, rec),
+                Err(err) => {
+                    self.read_errors.insert(msg.sender, err);
+                }
+            }
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    /// Add the records of one STDF file, in the order they appear in the file
+    pub fn add_records(&mut self, file_name: &str, records: impl IntoIterator<Item = StdfRecord>) {
+        for rec in records {
+            self.add_record(file_name, rec);
+        }
+    }
+
+    /// Add one record of a STDF file, records of different files can be interleaved
+    pub fn add_record(&mut self, file_name: &str, rec: StdfRecord) {
+        if !self.files.iter().any(|x| x == file_name) {
+            self.files.push(file_name.to_string());
+        }
+        *self.record_counts.entry(file_name.to_string()).or_default() += 1;
+
+        let ParametricBuilder {
+            options,
+            mir_cols,
+            sdr_cols,
+            vur_cols,
+            hbr_cols,
+            sbr_cols,
+            bin_records,
+            limit_cols,
+            ptr_cols,
+            mpr_cols,
+            ftr_cols,
+            str_cols,
+            pin_maps,
+            mpr_pins,
+            ptr_data,
+            ftr_data,
+            pf_data,
+            unit_cols,
+            header_cols,
+            long_data,
+            ftr_fail_data,
+            scan_maps,
+            scan_fail_data,
+            limit_log,
+            n_parts_observered,
+            prrs,
+            wir_cols,
+            wafer_cols,
+            wcr_cols,
+            part_wafer_ids,
+            tsr_cols,
+            test_sequence,
+            column_orders,
+            ..
+        } = self;
+
+        // test metadata is needed by the limits header and the test reports
+        let is_header_required =
+            options.limits_header || options.test_stats || options.test_summary;
+
+        match rec {
+            StdfRecord::MIR(mir) => {
+                mir_cols
+                    .entry(file_name.to_string())
+                    .and_modify(|_| println!("Multiple MIR in file, not supported"))
+                    .or_insert(mir);
+            }
+            StdfRecord::SDR(sdr) => {
+                // one SDR per site group is valid in STDF
+                sdr_cols.entry(file_name.to_string()).or_default().push(sdr);
+            }
+            StdfRecord::VUR(vur) => {
+                vur_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .push(vur.upd_nam);
+            }
+            StdfRecord::HBR(ref hbr) => {
+                bin_records
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .push(BinRecord {
+                        bin_type: "HBIN",
+                        head_num: hbr.head_num,
+                        site_num: hbr.site_num,
+                        bin_num: hbr.hbin_num,
+                        bin_cnt: hbr.hbin_cnt,
+                        bin_pf: hbr.hbin_pf,
+                        bin_nam: hbr.hbin_nam.clone(),
+                    });
+
+                hbr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry(hbr.hbin_num)
+                    .and_modify(|x| {
+                        if *x != hbr.hbin_nam {
+                            println!(
+                                "Multiple definitions for HBIN {}, using {}",
+                                hbr.hbin_num, x
+                            )
+                        }
+                    })
+                    .or_insert(hbr.hbin_nam.to_string());
+            }
+            StdfRecord::SBR(ref sbr) => {
+                bin_records
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .push(BinRecord {
+                        bin_type: "SBIN",
+                        head_num: sbr.head_num,
+                        site_num: sbr.site_num,
+                        bin_num: sbr.sbin_num,
+                        bin_cnt: sbr.sbin_cnt,
+                        bin_pf: sbr.sbin_pf,
+                        bin_nam: sbr.sbin_nam.clone(),
+                    });
+
+                sbr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry(sbr.sbin_num)
+                    .and_modify(|x| {
+                        if *x != sbr.sbin_nam {
+                            println!(
+                                "Multiple definitions for HBIN {}, using {}",
+                                sbr.sbin_num, x
+                            )
+                        }
+                    })
+                    .or_insert(sbr.sbin_nam.to_string());
+            }
+            StdfRecord::PMR(pmr) => {
+                pin_maps
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .add_pmr(pmr);
+            }
+            StdfRecord::PGR(pgr) => {
+                pin_maps
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .add_pgr(pgr);
+            }
+            StdfRecord::WIR(wir) => {
+                wir_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .insert(wir.head_num, wir);
+            }
+            StdfRecord::WRR(wrr) => {
+                let wir = wir_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .remove(&wrr.head_num);
+                wafer_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .push((wir, wrr));
+            }
+            StdfRecord::WCR(wcr) => {
+                wcr_cols
+                    .entry(file_name.to_string())
+                    .and_modify(|_| println!("Multiple WCR in file, not supported"))
+                    .or_insert(wcr);
+            }
+            StdfRecord::TSR(tsr) => {
+                tsr_cols.entry(file_name.to_string()).or_default().push(tsr);
+            }
+            StdfRecord::PIR(_) => {}
+            StdfRecord::PTR(ptr) => {
+                let test_key =
+                    [ptr.test_num.to_string(), ptr.test_txt.clone()].join(&options.separator);
+
+                test_sequence
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((ptr.head_num, ptr.site_num))
+                    .or_default()
+                    .push(test_key.clone());
+
+                // limits are shared by every site, unless each site keeps its own
+                //bit 0 set = RES_SCAL value is invalid. The default set by the first PTR with this test
+                // number will be used.
+                // bit 1 reserved for future used and must be 1.
+                // bit 2 set = No low specification limit.
+                // bit 3 set = No high specification limit.
+                // bit 4 set = LO_LIMIT and LLM_SCAL are invalid. The default values set for these fields
+                // in the first PTR with this test number will be used.
+                // bit 5 set = HI_LIMIT and HLM_SCAL are invalid. The default values set for these fields
+                // in the first PTR with this test number will be used.
+                // bit 6 set = No Low Limit for this test (LO_LIMIT and LLM_SCAL are invalid).
+                // bit 7 set = NoHigh Limit for this test (HI_LIMIT and HLM_SCAL are invalid).
+
+                let data = PtrOptionalData {
+                    opt_flag: ptr.opt_flag,
+                    res_scal: ptr.res_scal,
+                    llm_scal: ptr.llm_scal,
+                    hlm_scal: ptr.hlm_scal,
+                    lo_limit: ptr.lo_limit,
+                    hi_limit: ptr.hi_limit,
+                    units: ptr.units.clone(),
+                    _c_resfmt: ptr.c_resfmt.clone(),
+                    _c_llmfmt: ptr.c_llmfmt.clone(),
+                    _c_hlmfmt: ptr.c_hlmfmt.clone(),
+                    lo_spec: ptr.lo_spec,
+                    hi_spec: ptr.hi_spec,
+                };
+                let file_limits = limit_cols.entry(file_name.to_string()).or_default();
+                let site = (ptr.head_num, ptr.site_num);
+                add_limits(file_limits, site, &test_key, data, options);
+
+                // results are checked against the limits in force for this record
+                let limits = site_limits(file_limits, site, &test_key).clone();
+                ptr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry(site)
+                    .or_default()
+                    .push((ptr, limits));
+            }
+            StdfRecord::MPR(mpr) => {
+                let test_key =
+                    [mpr.test_num.to_string(), mpr.test_txt.clone()].join(&options.separator);
+
+                test_sequence
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((mpr.head_num, mpr.site_num))
+                    .or_default()
+                    .push(test_key.clone());
+
+                // RTN_ICNT of 0 means the default pins of the first MPR are used
+                if let Some(rtn_indx) = mpr.rtn_indx.clone().filter(|x| !x.is_empty()) {
+                    mpr_pins
+                        .entry(file_name.to_string())
+                        .or_default()
+                        .entry(test_key.clone())
+                        .or_insert(rtn_indx);
+                }
+
+                // MPR limit flags share the bit layout of the PTR OPT_FLAG,
+                // except bit 1 which flags START_IN and INCR_IN as invalid
+                let data = PtrOptionalData {
+                    opt_flag: mpr.opt_flag,
+                    res_scal: mpr.res_scal,
+                    llm_scal: mpr.llm_scal,
+                    hlm_scal: mpr.hlm_scal,
+                    lo_limit: mpr.lo_limit,
+                    hi_limit: mpr.hi_limit,
+                    units: mpr.units.clone(),
+                    _c_resfmt: mpr.c_resfmt.clone(),
+                    _c_llmfmt: mpr.c_llmfmt.clone(),
+                    _c_hlmfmt: mpr.c_hlmfmt.clone(),
+                    lo_spec: mpr.lo_spec,
+                    hi_spec: mpr.hi_spec,
+                };
+                let file_limits = limit_cols.entry(file_name.to_string()).or_default();
+                let site = (mpr.head_num, mpr.site_num);
+                add_limits(file_limits, site, &test_key, data, options);
+
+                // results are checked against the limits in force for this record
+                let limits = site_limits(file_limits, site, &test_key).clone();
+                mpr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry(site)
+                    .or_default()
+                    .push((mpr, limits));
+            }
+            StdfRecord::FTR(ftr) => {
+                test_sequence
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((ftr.head_num, ftr.site_num))
+                    .or_default()
+                    .push(
+                        [ftr.test_num.to_string(), ftr.test_txt.clone()].join(&options.separator),
+                    );
+
+                ftr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((ftr.head_num, ftr.site_num))
+                    .or_default()
+                    .push(ftr);
+            }
+            // V4-2007 scan fail datalog, kept only for the scan fail report
+            StdfRecord::STR(rec) if options.scan_fails => {
+                str_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((rec.head_num, rec.site_num))
+                    .or_default()
+                    .push(rec);
+            }
+            StdfRecord::PSR(psr) => {
+                scan_maps
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .add_psr(psr);
+            }
+            StdfRecord::NMR(nmr) => {
+                scan_maps
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .add_nmr(nmr);
+            }
+            StdfRecord::CNR(cnr) => {
+                scan_maps
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .add_cnr(cnr);
+            }
+            StdfRecord::SSR(ssr) => {
+                scan_maps
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .add_ssr(ssr);
+            }
+            StdfRecord::CDR(cdr) => {
+                scan_maps
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .add_cdr(cdr);
+            }
+            StdfRecord::PRR(prr) => {
+                // When we hit a PRR, we want to collate all the PTRs/FTRs
+                // which have occurred for this device.
+
+                // hashmap of files --> hashmap of columns with a 'vec' of test results
+
+                let parts_observed_in_file =
+                    n_parts_observered.entry(file_name.to_string()).or_insert(0);
+
+                let device_ptrs = ptr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((prr.head_num, prr.site_num))
+                    .or_default();
+
+                let device_mprs = mpr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((prr.head_num, prr.site_num))
+                    .or_default();
+
+                let device_ftrs = ftr_cols
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .entry((prr.head_num, prr.site_num))
+                    .or_default();
+
+                let all_ptr_results = ptr_data.entry(file_name.to_string()).or_default();
+                let all_ftr_results = ftr_data.entry(file_name.to_string()).or_default();
+                let all_pf_results = pf_data.entry(file_name.to_string()).or_default();
+                let all_units = unit_cols.entry(file_name.to_string()).or_default();
+                let all_headers = header_cols.entry(file_name.to_string()).or_default();
+                let long_rows = long_data.entry(file_name.to_string()).or_default();
+                let ftr_fail_rows = ftr_fail_data.entry(file_name.to_string()).or_default();
+
+                let site = (prr.head_num, prr.site_num);
+                let file_limit_log = limit_log.entry(file_name.to_string()).or_default();
+                let limit_scope = Some(site).filter(|_| options.per_site_limits);
+
+                // result columns of each test of this part
+                let mut device_columns: HashMap<ColumnName, Vec<(u32, ColumnName)>> =
+                    HashMap::new();
+
+                device_ptrs.iter().for_each(|(x, ptr_optional_data)| {
+                    let test_key =
+                        [x.test_num.to_string(), x.test_txt.clone()].join(&options.separator);
+
+                    device_columns
+                        .entry(test_key.clone())
+                        .or_default()
+                        .push((x.test_num, test_key.clone()));
+
+                    // get all of the current PTR results in a Vec
+                    let ptr_results = all_ptr_results.entry(test_key.clone()).or_default();
+
+                    let pf_results = all_pf_results
+                        .entry([("PF").to_string(), test_key.clone()].join(&options.separator))
+                        .or_default();
+
+                    // extend the vec with `None` values if required
+                    let current_num_ptr_observations = ptr_results.len();
+
+                    if current_num_ptr_observations < *parts_observed_in_file {
+                        let elements_to_add =
+                            *parts_observed_in_file - current_num_ptr_observations;
+                        let padding: Vec<Option<f32>> = vec![None; elements_to_add];
+                        ptr_results.extend(padding);
+
+                        let padding_pf: Vec<Option<u32>> = vec![None; elements_to_add];
+                        pf_results.extend(padding_pf);
+                    }
+
+                    if options.dynamic_limits {
+                        log_limits(
+                            file_limit_log
+                                .entry((test_key.clone(), limit_scope))
+                                .or_default(),
+                            *parts_observed_in_file,
+                            test_header(
+                                x.test_num,
+                                x.test_txt.clone(),
+                                ptr_optional_data,
+                                options.scaled_results,
+                            ),
+                        );
+                    }
+
+                    // Add the result for this PTR, null when the tester flags it
+                    // invalid or not executed
+                    let is_valid = is_valid_result(x.test_flg[0]);
+                    let pass = Some(is_within_limits(x.result, ptr_optional_data, x.parm_flg[0]))
+                        .filter(|_| is_valid);
+
+                    if !is_valid {
+                        ptr_results.push(None);
+                    } else if options.scaled_results {
+                        ptr_results.push(Some(scale_value(x.result, ptr_optional_data.res_scal)));
+
+                        if let Some(units) =
+                            scaled_units(&ptr_optional_data.units, ptr_optional_data.res_scal)
+                        {
+                            all_units.insert(test_key.clone(), units);
+                        }
+                    } else {
+                        ptr_results.push(Some(x.result));
+                    }
+
+                    pf_results.push(pass.map(|x| x as u32));
+
+                    if options.tester_pass_fail {
+                        let tpf_results = all_pf_results
+                            .entry([("TPF").to_string(), test_key.clone()].join(&options.separator))
+                            .or_default();
+                        if tpf_results.len() < *parts_observed_in_file {
+                            tpf_results.resize(*parts_observed_in_file, None);
+                        }
+                        tpf_results.push(tester_pass_fail(x.test_flg[0]).map(|x| x as u32));
+                    }
+
+                    if options.long_format {
+                        long_rows.push(
+                            *parts_observed_in_file,
+                            &prr,
+                            test_header(
+                                x.test_num,
+                                x.test_txt.clone(),
+                                ptr_optional_data,
+                                options.scaled_results,
+                            ),
+                            *ptr_results.last().unwrap(),
+                            pass,
+                        );
+                    }
+
+                    if is_header_required && !all_headers.contains_key(&test_key) {
+                        let header = test_header(
+                            x.test_num,
+                            x.test_txt.clone(),
+                            ptr_optional_data,
+                            options.scaled_results,
+                        );
+                        let pf_header = TestHeader {
+                            units: None,
+                            lo_limit: None,
+                            hi_limit: None,
+                            lo_spec: None,
+                            hi_spec: None,
+                            ..header.clone()
+                        };
+                        if options.tester_pass_fail {
+                            all_headers.insert(
+                                [("TPF").to_string(), test_key.clone()].join(&options.separator),
+                                pf_header.clone(),
+                            );
+                        }
+                        all_headers.insert(
+                            [("PF").to_string(), test_key.clone()].join(&options.separator),
+                            pf_header,
+                        );
+                        all_headers.insert(test_key, header);
+                    }
+                });
+
+                device_ptrs.clear();
+
+                // MPR implementation, one column per returned pin result
+                let pin_map = pin_maps.get(file_name);
+                let default_pins = mpr_pins.entry(file_name.to_string()).or_default();
+
+                device_mprs.iter().for_each(|(x, ptr_optional_data)| {
+                    let test_key =
+                        [x.test_num.to_string(), x.test_txt.clone()].join(&options.separator);
+
+                    let pins = x
+                        .rtn_indx
+                        .as_ref()
+                        .filter(|pins| !pins.is_empty())
+                        .or_else(|| default_pins.get(&test_key));
+
+                    if options.dynamic_limits {
+                        log_limits(
+                            file_limit_log
+                                .entry((test_key.clone(), limit_scope))
+                                .or_default(),
+                            *parts_observed_in_file,
+                            test_header(
+                                x.test_num,
+                                x.test_txt.clone(),
+                                ptr_optional_data,
+                                options.scaled_results,
+                            ),
+                        );
+                    }
+
+                    x.rtn_rslt.iter().enumerate().for_each(|(i, result)| {
+                        let pin = match pins.and_then(|pins| pins.get(i)) {
+                            Some(index) => pin_name(pin_map, *index),
+                            None => i.to_string(),
+                        };
+                        let pin_key = [test_key.clone(), pin.clone()].join(&options.separator);
+
+                        device_columns
+                            .entry(test_key.clone())
+                            .or_default()
+                            .push((x.test_num, pin_key.clone()));
+
+                        let mpr_results = all_ptr_results.entry(pin_key.clone()).or_default();
+
+                        let pf_results = all_pf_results
+                            .entry([("PF").to_string(), pin_key.clone()].join(&options.separator))
+                            .or_default();
+
+                        let num_observations = mpr_results.len();
+
+                        if num_observations < *parts_observed_in_file {
+                            let elements_to_add = *parts_observed_in_file - num_observations;
+                            let padding: Vec<Option<f32>> = vec![None; elements_to_add];
+                            let padding_pf: Vec<Option<u32>> = vec![None; elements_to_add];
+                            mpr_results.extend(padding);
+                            pf_results.extend(padding_pf);
+                        }
+
+                        let is_valid = is_valid_result(x.test_flg[0]);
+                        let pass =
+                            Some(is_within_limits(*result, ptr_optional_data, x.parm_flg[0]))
+                                .filter(|_| is_valid);
+
+                        if !is_valid {
+                            mpr_results.push(None);
+                        } else if options.scaled_results {
+                            mpr_results
+                                .push(Some(scale_value(*result, ptr_optional_data.res_scal)));
+
+                            if let Some(units) =
+                                scaled_units(&ptr_optional_data.units, ptr_optional_data.res_scal)
+                            {
+                                all_units.insert(pin_key.clone(), units);
+                            }
+                        } else {
+                            mpr_results.push(Some(*result));
+                        }
+                        pf_results.push(pass.map(|x| x as u32));
+
+                        if options.tester_pass_fail {
+                            let tpf_results = all_pf_results
+                                .entry(
+                                    [("TPF").to_string(), pin_key.clone()].join(&options.separator),
+                                )
+                                .or_default();
+                            if tpf_results.len() < *parts_observed_in_file {
+                                tpf_results.resize(*parts_observed_in_file, None);
+                            }
+                            tpf_results.push(tester_pass_fail(x.test_flg[0]).map(|x| x as u32));
+                        }
+
+                        if options.long_format {
+                            long_rows.push(
+                                *parts_observed_in_file,
+                                &prr,
+                                test_header(
+                                    x.test_num,
+                                    [x.test_txt.clone(), pin.clone()].join(&options.separator),
+                                    ptr_optional_data,
+                                    options.scaled_results,
+                                ),
+                                *mpr_results.last().unwrap(),
+                                pass,
+                            );
+                        }
+
+                        if is_header_required && !all_headers.contains_key(&pin_key) {
+                            let header = test_header(
+                                x.test_num,
+                                [x.test_txt.clone(), pin].join(&options.separator),
+                                ptr_optional_data,
+                                options.scaled_results,
+                            );
+                            let pf_header = TestHeader {
+                                units: None,
+                                lo_limit: None,
+                                hi_limit: None,
+                                lo_spec: None,
+                                hi_spec: None,
+                                ..header.clone()
+                            };
+                            if options.tester_pass_fail {
+                                all_headers.insert(
+                                    [("TPF").to_string(), pin_key.clone()].join(&options.separator),
+                                    pf_header.clone(),
+                                );
+                            }
+                            all_headers.insert(
+                                [("PF").to_string(), pin_key.clone()].join(&options.separator),
+                                pf_header,
+                            );
+                            all_headers.insert(pin_key, header);
+                        }
+                    });
+                });
+
+                device_mprs.clear();
+
+                // FTR implementation
+                device_ftrs.iter().for_each(|x| {
+                    let test_key =
+                        [x.test_num.to_string(), x.test_txt.clone()].join(&options.separator);
+
+                    device_columns
+                        .entry(test_key.clone())
+                        .or_default()
+                        .push((x.test_num, test_key.clone()));
+
+                    let ftr_results = all_ftr_results.entry(test_key.clone()).or_default();
+
+                    let pf_results = all_pf_results
+                        .entry([("PF").to_string(), test_key.clone()].join(&options.separator))
+                        .or_default();
+
+                    let num_observations = ftr_results.len();
+
+                    if num_observations < *parts_observed_in_file {
+                        let elements_to_add = *parts_observed_in_file - num_observations;
+                        let padding: Vec<Option<u32>> = vec![None; elements_to_add];
+                        let padding_pf: Vec<Option<u32>> = vec![None; elements_to_add];
+                        ftr_results.extend(padding);
+                        pf_results.extend(padding_pf);
+                    }
+
+                    ftr_results.push(Some(x.test_flg[0] as u32));
+                    pf_results.push(Some((x.test_flg[0] == 0) as u32));
+
+                    if options.ftr_fails && tester_pass_fail(x.test_flg[0]) == Some(false) {
+                        ftr_fail_rows.push(*parts_observed_in_file, &prr, x, pin_map);
+                    }
+
+                    if options.long_format && options.functional_tests {
+                        long_rows.push(
+                            *parts_observed_in_file,
+                            &prr,
+                            TestHeader {
+                                test_num: x.test_num,
+                                test_txt: x.test_txt.clone(),
+                                units: None,
+                                lo_limit: None,
+                                hi_limit: None,
+                                lo_spec: None,
+                                hi_spec: None,
+                                res_scal: None,
+                            },
+                            Some(x.test_flg[0] as f32),
+                            Some(x.test_flg[0] == 0),
+                        );
+                    }
+
+                    if is_header_required && !all_headers.contains_key(&test_key) {
+                        let header = TestHeader {
+                            test_num: x.test_num,
+                            test_txt: x.test_txt.clone(),
+                            units: None,
+                            lo_limit: None,
+                            hi_limit: None,
+                            lo_spec: None,
+                            hi_spec: None,
+                            res_scal: None,
+                        };
+                        all_headers.insert(
+                            [("PF").to_string(), test_key.clone()].join(&options.separator),
+                            header.clone(),
+                        );
+                        all_headers.insert(test_key, header);
+                    }
+                });
+
+                device_ftrs.clear();
+
+                // STR scan fails of this part
+                if let Some(device_strs) = str_cols
+                    .get_mut(file_name)
+                    .and_then(|x| x.get_mut(&(prr.head_num, prr.site_num)))
+                {
+                    let scan_fail_rows = scan_fail_data.entry(file_name.to_string()).or_default();
+                    for rec in device_strs.iter() {
+                        scan_fail_rows.push(*parts_observed_in_file, &prr, rec);
+                    }
+                    device_strs.clear();
+                }
+
+                // add columns first seen in this part to the column order,
+                // following the order their tests were executed in
+                let part_columns: Vec<(u32, ColumnName)> = test_sequence
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .remove(&(prr.head_num, prr.site_num))
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|test_key| device_columns.remove(test_key))
+                    .flatten()
+                    .collect();
+                column_orders
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .merge(&part_columns);
+
+                // parts between WIR and WRR belong to that wafer
+                let wafer_id = wir_cols
+                    .get(file_name)
+                    .and_then(|wirs| wirs.get(&prr.head_num))
+                    .map(|wir| wir.wafer_id.clone());
+                part_wafer_ids
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .push(wafer_id);
+
+                prrs.entry(file_name.to_string()).or_default().push(prr);
+                *parts_observed_in_file += 1;
+            }
+            _ => {}
+        }
+    }
+
+    /// Build the reports of every file added so far
+    pub fn build(mut self) -> Result<Report, Error> {
+        let mut yield_counts: HashMap<YieldKey, YieldCounts> = HashMap::new();
+        let mut lot_bins: HashMap<LotBinKey, BinRow> = HashMap::new();
+        let mut file_reports: Vec<FileReport> = vec![];
+        let mut status: HashMap<FileName, FileStatus> = HashMap::new();
+        let mut report_order = ColumnOrder::default();
+
+        let (lot_retests, lot_group_sizes) = if self.options.yield_summary {
+            self.lot_retests()
+        } else {
+            Default::default()
+        };
+
+        // files are reported in the order they were given
+        for k in self.files.clone() {
+            if !self.record_counts.contains_key(&k) {
+                let err = self
+                    .read_errors
+                    .remove(&k)
+                    .unwrap_or_else(|| Error::EmptyFile(k.clone()));
+                status.insert(k, FileStatus::Failed(err));
+                continue;
+            }
+
+            self.check_metadata(&k);
+            self.pad_results(&k);
+
+            let lot_id = self.lot_id(&k);
+            let retests = retest_groups(self.file_prrs(&k).iter().zip(self.file_wafer_ids(&k)));
+            let keep = kept_parts(&retests, self.options.retest);
+
+            if self.options.yield_summary {
+                self.count_yield(
+                    &k,
+                    &retests,
+                    &lot_retests[&k],
+                    &lot_group_sizes[&lot_id],
+                    &mut yield_counts,
+                );
+            }
+
+            let test_columns = self.test_columns(&k);
+            let df = if self.options.long_format {
+                self.long_data
+                    .remove(&k)
+                    .unwrap_or_default()
+                    .into_df(&k, &retests, &keep)?
+            } else {
+                let report_columns: Vec<(u32, ColumnName)> = test_columns
+                    .iter()
+                    .map(|(test_num, s)| (*test_num, s.name().to_string()))
+                    .collect();
+                report_order.merge(&report_columns);
+
+                self.parametric_df(&k, test_columns, &retests, &keep)?
+            };
+
+            let stats_df = if self.options.test_stats {
+                Some(test_stats_df(
+                    &k,
+                    &lot_id,
+                    &self.test_stats_rows(&k, &keep),
+                )?)
+            } else {
+                None
+            };
+
+            let limits_df = if self.options.dynamic_limits {
+                Some(limit_changes_df(&k, &lot_id, &self.limit_ranges(&k))?)
+            } else {
+                None
+            };
+
+            let tsr_df = if self.options.test_summary {
+                Some(test_summary_df(&k, &lot_id, &self.tsr_rows(&k))?)
+            } else {
+                None
+            };
+
+            let pin_map_df = match self.pin_maps.get(&k) {
+                Some(pin_map) if self.options.pin_map && !pin_map.is_empty() => {
+                    Some(pin_map.pin_map_df(&k)?)
+                }
+                _ => None,
+            };
+
+            let scan_fails_df = if self.options.scan_fails {
+                Some(self.scan_fail_data.remove(&k).unwrap_or_default().into_df(
+                    &k,
+                    &retests,
+                    &keep,
+                    &self.scan_maps.remove(&k).unwrap_or_default(),
+                    self.pin_maps.get(&k),
+                )?)
+            } else {
+                None
+            };
+
+            let ftr_fails_df = if self.options.ftr_fails {
+                Some(
+                    self.ftr_fail_data
+                        .remove(&k)
+                        .unwrap_or_default()
+                        .into_df(&k, &retests, &keep)?,
+                )
+            } else {
+                None
+            };
+
+            let wafer_df = match self.wafer_cols.remove(&k) {
+                Some(wafers) => Some(wafer_summary_df(
+                    &k,
+                    &lot_id,
+                    &wafers,
+                    self.file_wafer_ids(&k),
+                )?),
+                None => None,
+            };
+
+            let bin_df = if self.options.bin_summary {
+                let rows = self.bin_rows(&k, &lot_id);
+                add_lot_bins(&mut lot_bins, &rows);
+                Some(bin_summary_df(&rows, Some(&k))?)
+            } else {
+                None
+            };
+
+            let file_headers = self.report_headers(&k);
+            status.insert(k.clone(), self.file_status(&k));
+
+            file_reports.push(FileReport {
+                file_name: k,
+                parametric: df,
+                headers: file_headers,
+                wafer: wafer_df,
+                bins: bin_df,
+                test_stats: stats_df,
+                test_summary: tsr_df,
+                limit_changes: limits_df,
+                ftr_fails: ftr_fails_df,
+                pin_map: pin_map_df,
+                scan_fails: scan_fails_df,
+                is_limits_header: self.options.limits_header && !self.options.long_format,
+            });
+        }
+
+        // yield is reported for every level in one combined report
+        let yield_summary = if self.options.yield_summary {
+            let mut counts: Vec<(YieldKey, YieldCounts)> = yield_counts.into_iter().collect();
+            counts.sort_by(|a, b| a.0.cmp(&b.0));
+
+            Some(yield_df(&counts)?)
+        } else {
+            None
+        };
+
+        // lots can span several files, so the lot bin summary is always combined
+        let lot_bins = if self.options.bin_summary {
+            let mut rows: Vec<BinRow> = lot_bins.into_values().collect();
+            rows.sort_by(|a, b| {
+                (&a.lot_id, a.bin_type, a.bin_num, a.head_num, a.site_num)
+                    .cmp(&(&b.lot_id, b.bin_type, b.bin_num, b.head_num, b.site_num))
+            });
+
+            Some(bin_summary_df(&rows, None)?)
+        } else {
+            None
+        };
+
+        Ok(Report {
+            files: file_reports,
+            status: self
+                .files
+                .into_iter()
+                .map(|file_name| {
+                    let file_status = status.remove(&file_name).unwrap();
+                    (file_name, file_status)
+                })
+                .collect(),
+            yield_summary,
+            lot_bins,
+            test_columns: report_order
+                .ordered(self.options.test_order)
+                .into_iter()
+                .map(|(_, tname)| tname)
+                .collect(),
+            is_limits_header: self.options.limits_header && !self.options.long_format,
+        })
+    }
+
+    fn lot_id(&self, file_name: &str) -> String {
+        self.mir_cols
+            .get(file_name)
+            .map(|x| x.lot_id.clone())
+            .unwrap_or_default()
+    }
+
+    fn file_prrs(&self, file_name: &str) -> &[rust_stdf::PRR] {
+        self.prrs
+            .get(file_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn file_wafer_ids(&self, file_name: &str) -> &[Option<String>] {
+        self.part_wafer_ids
+            .get(file_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn n_parts(&self, file_name: &str) -> usize {
+        self.n_parts_observered.get(file_name).copied().unwrap_or(0)
+    }
+
+    fn display_name(&self, file_name: &str, tname: &ColumnName) -> ColumnName {
+        match self.unit_cols.get(file_name).and_then(|x| x.get(tname)) {
+            Some(unit) => format!("{} [{}]", tname, unit),
+            None => tname.clone(),
+        }
+    }
+
+    // a part may be retested in another file of its lot, so lot yield
+    // groups the retests of every file of the lot
+    fn lot_retests(&self) -> (HashMap<FileName, RetestGroups>, HashMap<String, GroupSizes>) {
+        let mut lot_files: HashMap<String, Vec<&FileName>> = HashMap::new();
+        for k in self
+            .files
+            .iter()
+            .filter(|k| self.record_counts.contains_key(*k))
+        {
+            lot_files.entry(self.lot_id(k)).or_default().push(k);
+        }
+
+        let mut lot_retests: HashMap<FileName, RetestGroups> = HashMap::new();
+        let mut lot_group_sizes: HashMap<String, GroupSizes> = HashMap::new();
+        for (lot_id, lot_files) in lot_files {
+            let retests = retest_groups(
+                lot_files
+                    .iter()
+                    .flat_map(|k| self.file_prrs(k).iter().zip(self.file_wafer_ids(k))),
+            );
+            lot_group_sizes.insert(lot_id, group_sizes(&retests));
+
+            let mut retests = retests.into_iter();
+            for k in lot_files {
+                let n_prrs = self.file_prrs(k).len();
+                lot_retests.insert(k.clone(), retests.by_ref().take(n_prrs).collect());
+            }
+        }
+
+        (lot_retests, lot_group_sizes)
+    }
+
+    // MIR fields are null in the reports of a file without MIR,
+    // SDR fields are null for sites no SDR describes
+    fn check_metadata(&self, file_name: &str) {
+        if !self.mir_cols.contains_key(file_name) {
+            println!(
+                "No MIR in file, reporting without lot information :: {}",
+                file_name
+            );
+        }
+
+        let sdrs: &[rust_stdf::SDR] = self
+            .sdr_cols
+            .get(file_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        if !sdrs.is_empty()
+            && self
+                .file_prrs(file_name)
+                .iter()
+                .any(|prr| sdr_for_site(sdrs, prr.head_num, prr.site_num).is_none())
+        {
+            println!(
+                "Parts on sites no SDR describes, reporting without their SDR information :: {}",
+                file_name
+            );
+        }
+    }
+
+    // results are only padded when a test is seen again, so pad the tests
+    // missing from the final parts to the part count
+    fn pad_results(&mut self, file_name: &str) {
+        let total_parts = self.n_parts(file_name);
+
+        for results in self
+            .ptr_data
+            .entry(file_name.to_string())
+            .or_default()
+            .values_mut()
+        {
+            if results.len() < total_parts {
+                results.resize(total_parts, None);
+            }
+        }
+        let file_ftrs = self.ftr_data.entry(file_name.to_string()).or_default();
+        let file_pfs = self.pf_data.entry(file_name.to_string()).or_default();
+        for results in file_ftrs.values_mut().chain(file_pfs.values_mut()) {
+            if results.len() < total_parts {
+                results.resize(total_parts, None);
+            }
+        }
+    }
+
+    fn count_yield(
+        &self,
+        file_name: &str,
+        retests: &[(usize, u32)],
+        lot_retests: &[(usize, u32)],
+        lot_sizes: &GroupSizes,
+        yield_counts: &mut HashMap<YieldKey, YieldCounts>,
+    ) {
+        let lot_id = self.lot_id(file_name);
+        let group_sizes = group_sizes(retests);
+
+        // bit 3 set = part failed, unless bit 4 says there is no pass/fail
+        // indication, in which case the HBR pass/fail flag is used
+        let hbin_pf: HashMap<BinNum, char> = self
+            .bin_records
+            .get(file_name)
+            .into_iter()
+            .flatten()
+            .filter(|x| x.bin_type == "HBIN")
+            .map(|x| (x.bin_num, x.bin_pf))
+            .collect();
+        let is_good = |prr: &rust_stdf::PRR| match prr.part_flg[0] & 0b0001_1000 {
+            0b0000_0000 => true,
+            0b0000_1000 => false,
+            _ => hbin_pf.get(&prr.hard_bin) == Some(&'P'),
+        };
+
+        for ((prr, (group, index)), (lot_group, lot_index)) in self
+            .file_prrs(file_name)
+            .iter()
+            .zip(retests)
+            .zip(lot_retests)
+        {
+            let good = is_good(prr);
+            let mut count = |key: YieldKey, is_first: bool, is_last: bool| {
+                let counts = yield_counts.entry(key).or_default();
+                counts.tested += 1;
+                counts.parts += is_last as u32;
+                counts.good += (is_last && good) as u32;
+                counts.first_parts += is_first as u32;
+                counts.first_good += (is_first && good) as u32;
+            };
+
+            count(
+                (lot_id.clone(), None, None, None),
+                *lot_index == 0,
+                *lot_index + 1 == lot_sizes[lot_group],
+            );
+
+            let is_first = *index == 0;
+            let is_last = *index + 1 == group_sizes[group];
+            let keys: [YieldKey; 3] = [
+                (lot_id.clone(), Some(file_name.to_string()), None, None),
+                (
+                    lot_id.clone(),
+                    Some(file_name.to_string()),
+                    Some(prr.head_num),
+                    None,
+                ),
+                (
+                    lot_id.clone(),
+                    Some(file_name.to_string()),
+                    Some(prr.head_num),
+                    Some(prr.site_num),
+                ),
+            ];
+
+            for key in keys {
+                count(key, is_first, is_last);
+            }
+        }
+    }
+
+    // each test column is followed by its pass/fail column
+    fn test_columns(&self, file_name: &str) -> Vec<(u32, Series)> {
+        let options = &self.options;
+        let file_ptrs = &self.ptr_data[file_name];
+        let file_ftrs = &self.ftr_data[file_name];
+        let file_pfs = &self.pf_data[file_name];
+
+        let mut test_columns: Vec<(u32, Series)> = vec![];
+        for (test_num, tname) in self
+            .column_orders
+            .get(file_name)
+            .map(|x| x.ordered(options.test_order))
+            .unwrap_or_default()
+        {
+            if let Some(data) = file_ptrs.get(&tname) {
+                test_columns.push((
+                    test_num,
+                    Series::new(&self.display_name(file_name, &tname), data),
+                ));
+            } else if let Some(data) = file_ftrs.get(&tname) {
+                if options.functional_tests {
+                    test_columns.push((test_num, Series::new(&tname, data)));
+                }
+            }
+
+            let pf_key = [("PF").to_string(), tname.clone()].join(&options.separator);
+            if let Some(data) = file_pfs.get(&pf_key) {
+                if options.pass_fail_columns {
+                    test_columns.push((test_num, Series::new(&pf_key, data)));
+                }
+            }
+
+            // the tester's pass/fail follows the recomputed one
+            let tpf_key = [("TPF").to_string(), tname.clone()].join(&options.separator);
+            if let Some(data) = file_pfs.get(&tpf_key) {
+                let n_mismatches = file_pfs.get(&pf_key).map_or(0, |pfs| {
+                    pfs.iter()
+                        .zip(data)
+                        .filter(|(pf, tpf)| pf.is_some() && tpf.is_some() && pf != tpf)
+                        .count()
+                });
+                if n_mismatches > 0 {
+                    println!(
+                        "tester pass/fail differs from limits :: {} :: {} :: {} parts",
+                        file_name, tname, n_mismatches
+                    );
+                }
+                test_columns.push((test_num, Series::new(&tpf_key, data)));
+            }
+        }
+
+        test_columns
+    }
+
+    // one row per part, the part and file information followed by the test columns
+    fn parametric_df(
+        &self,
+        file_name: &str,
+        test_columns: Vec<(u32, Series)>,
+        retests: &[(usize, u32)],
+        keep: &[bool],
+    ) -> PolarsResult<DataFrame> {
+        let mut fields = self.file_columns(file_name);
+        fields.extend(self.sdr_columns(file_name));
+        fields.extend(self.part_columns(file_name, retests));
+        fields.extend(self.wcr_columns(file_name));
+        fields.extend(test_columns.into_iter().map(|(_, s)| s));
+
+        // drop superseded tests of retested parts before building the report
+        if self.options.retest != RetestPolicy::All {
+            let mask: BooleanChunked = keep.iter().copied().collect();
+            fields = fields
+                .iter()
+                .map(|s| s.filter(&mask))
+                .collect::<PolarsResult<Vec<Series>>>()?;
+        }
+
+        DataFrame::new(fields)
+    }
+
+    // the file name, and the lot information of the MIR and VUR
+    fn file_columns(&self, file_name: &str) -> Vec<Series> {
+        let total_parts = self.n_parts(file_name);
+        let mir = self.mir_cols.get(file_name);
+        let mir_values = |field: fn(&rust_stdf::MIR) -> String| -> Vec<Option<String>> {
+            vec![mir.map(field); total_parts]
+        };
+
+        vec![
+            Series::new("File Name", vec![file_name; total_parts]),
+            Series::new("Lot ID", mir_values(|x| x.lot_id.clone())),
+            Series::new("Serial Num", mir_values(|x| x.serl_num.clone())),
+            Series::new(
+                "Setup Time",
+                mir_values(|x| Utc.timestamp_opt(x.setup_t.into(), 0).unwrap().to_rfc3339()),
+            ),
+            Series::new("Part Type", mir_values(|x| x.part_typ.clone())),
+            Series::new("Design Rev", mir_values(|x| x.dsgn_rev.clone())),
+            Series::new("Package Type", mir_values(|x| x.pkg_typ.clone())),
+            Series::new("Facility ID", mir_values(|x| x.facil_id.clone())),
+            Series::new("Process ID", mir_values(|x| x.proc_id.clone())),
+            Series::new("Flow ID", mir_values(|x| x.flow_id.clone())),
+            Series::new("Job Name", mir_values(|x| x.job_nam.clone())),
+            Series::new("Job Rev", mir_values(|x| x.job_rev.clone())),
+            Series::new("Operator Name", mir_values(|x| x.oper_nam.clone())),
+            Series::new("Tester Type", mir_values(|x| x.tstr_typ.clone())),
+            Series::new(
+                "Station Num",
+                vec![mir.map(|x| x.stat_num as u32); total_parts],
+            ),
+            Series::new("Exec Version", mir_values(|x| x.exec_ver.clone())),
+            Series::new("Test Code", mir_values(|x| x.test_cod.clone())),
+            Series::new("Mode Code", mir_values(|x| x.mode_cod.to_string())),
+            Series::new("Test Temperature", mir_values(|x| x.tst_temp.clone())),
+            Series::new("Spec Name", mir_values(|x| x.spec_nam.clone())),
+            Series::new("Spec Version", mir_values(|x| x.spec_ver.clone())),
+            // null for files before V4-2007, which have no VUR
+            Series::new(
+                "STDF Version Update",
+                vec![self.vur_cols.get(file_name).map(|x| x.join(",")); total_parts],
+            ),
+        ]
+    }
+
+    // the SDR of the site of each part
+    fn sdr_columns(&self, file_name: &str) -> Vec<Series> {
+        let sdrs: &[rust_stdf::SDR] = self
+            .sdr_cols
+            .get(file_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let part_sdrs: Vec<Option<&rust_stdf::SDR>> = self
+            .file_prrs(file_name)
+            .iter()
+            .map(|prr| sdr_for_site(sdrs, prr.head_num, prr.site_num))
+            .collect();
+        let sdr_values = |field: fn(&rust_stdf::SDR) -> &String| -> Vec<Option<String>> {
+            part_sdrs
+                .iter()
+                .map(|sdr| sdr.map(|x| field(x).clone()))
+                .collect()
+        };
+
+        vec![
+            Series::new("Handler ID", sdr_values(|sdr| &sdr.hand_id)),
+            Series::new("Handler Type", sdr_values(|sdr| &sdr.hand_typ)),
+            Series::new("Loadboard ID", sdr_values(|sdr| &sdr.load_id)),
+            Series::new("Cont ID", sdr_values(|sdr| &sdr.cont_id)),
+            Series::new("DIB Type", sdr_values(|sdr| &sdr.dib_typ)),
+            Series::new("DIB ID", sdr_values(|sdr| &sdr.dib_id)),
+        ]
+    }
+
+    // the PRR fields of each part, with its retest index and wafer
+    fn part_columns(&self, file_name: &str, retests: &[(usize, u32)]) -> Vec<Series> {
+        let prrs = self.file_prrs(file_name);
+        let prr_values =
+            |field: fn(&rust_stdf::PRR) -> u32| -> Vec<u32> { prrs.iter().map(field).collect() };
+        // -32768 indicates the coordinate is invalid
+        let coord_values = |field: fn(&rust_stdf::PRR) -> i16| -> Vec<Option<i32>> {
+            prrs.iter()
+                .map(|prr| Some(field(prr) as i32).filter(|x| *x != i16::MIN as i32))
+                .collect()
+        };
+
+        // bit 0 set = retest of a part with the same PART_ID
+        // bit 1 set = retest of a part with the same X/Y coordinates
+        // bit 2 set = abnormal end of testing
+        // bit 3 set = part failed
+        // bit 4 set = no pass/fail indication, bit 3 is invalid
+        let part_flg_values = prr_values(|prr| prr.part_flg[0] as u32);
+        let retest_values: Vec<bool> = part_flg_values
+            .iter()
+            .map(|flg| flg & 0b0000_0011 != 0)
+            .collect();
+        let abnormal_values: Vec<bool> = part_flg_values
+            .iter()
+            .map(|flg| flg & 0b0000_0100 != 0)
+            .collect();
+        let failed_values: Vec<Option<bool>> = part_flg_values
+            .iter()
+            .map(|flg| Some(flg & 0b0000_1000 != 0).filter(|_| flg & 0b0001_0000 == 0))
+            .collect();
+
+        let bin_description = |bins: &HashMap<FileName, HashMap<BinNum, BinDescription>>,
+                               bin_num: BinNum| {
+            bins.get(file_name)
+                .and_then(|x| x.get(&bin_num))
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        vec![
+            Series::new(
+                "Part ID",
+                prrs.iter()
+                    .map(|prr| prr.part_id.clone())
+                    .collect::<Vec<String>>(),
+            ),
+            Series::new(
+                "Part TXT",
+                prrs.iter()
+                    .map(|prr| prr.part_txt.clone())
+                    .collect::<Vec<String>>(),
+            ),
+            Series::new("Head Num", prr_values(|prr| prr.head_num as u32)),
+            Series::new("Site Num", prr_values(|prr| prr.site_num as u32)),
+            Series::new("X Coord", coord_values(|prr| prr.x_coord)),
+            Series::new("Y Coord", coord_values(|prr| prr.y_coord)),
+            Series::new("Test Time", prr_values(|prr| prr.test_t)),
+            Series::new("Num Tests", prr_values(|prr| prr.num_test as u32)),
+            Series::new("Part Flag", part_flg_values),
+            Series::new("Retest", retest_values),
+            Series::new(
+                "Retest Index",
+                retests
+                    .iter()
+                    .map(|(_, index)| *index)
+                    .collect::<Vec<u32>>(),
+            ),
+            Series::new("Abnormal End", abnormal_values),
+            Series::new("Part Failed", failed_values),
+            Series::new("HBIN", prr_values(|prr| prr.hard_bin as u32)),
+            Series::new(
+                "HBIN Description",
+                prrs.iter()
+                    .map(|prr| bin_description(&self.hbr_cols, prr.hard_bin))
+                    .collect::<Vec<BinDescription>>(),
+            ),
+            Series::new("SBIN", prr_values(|prr| prr.soft_bin as u32)),
+            Series::new(
+                "SBIN Description",
+                prrs.iter()
+                    .map(|prr| bin_description(&self.sbr_cols, prr.soft_bin))
+                    .collect::<Vec<BinDescription>>(),
+            ),
+            Series::new("Wafer ID", self.file_wafer_ids(file_name)),
+        ]
+    }
+
+    // wafer geometry, only present for wafer sort
+    fn wcr_columns(&self, file_name: &str) -> Vec<Series> {
+        let total_parts = self.n_parts(file_name);
+        self.wcr_cols
+            .get(file_name)
+            .map(|wcr| {
+                // 0 and -32768 indicate a missing size or center, a space
+                // a missing flat or axis direction
+                let size = |x: f32| Some(x).filter(|x| *x != 0.0);
+                let center = |x: i16| Some(x as i32).filter(|x| *x != i16::MIN as i32);
+                let direction = |x: char| Some(x.to_string()).filter(|x| x != " ");
+                let wf_units = match wcr.wf_units {
+                    1 => Some("inches"),
+                    2 => Some("cm"),
+                    3 => Some("mm"),
+                    4 => Some("mils"),
+                    _ => None,
+                };
+
+                vec![
+                    Series::new("Wafer Size", vec![size(wcr.wafr_siz); total_parts]),
+                    Series::new("Die Height", vec![size(wcr.die_ht); total_parts]),
+                    Series::new("Die Width", vec![size(wcr.die_wid); total_parts]),
+                    Series::new("Wafer Units", vec![wf_units; total_parts]),
+                    Series::new("Wafer Flat", vec![direction(wcr.wf_flat); total_parts]),
+                    Series::new("Center X", vec![center(wcr.center_x); total_parts]),
+                    Series::new("Center Y", vec![center(wcr.center_y); total_parts]),
+                    Series::new("Positive X", vec![direction(wcr.pos_x); total_parts]),
+                    Series::new("Positive Y", vec![direction(wcr.pos_y); total_parts]),
+                ]
+            })
+            .unwrap_or_default()
+    }
+
+    // per-test statistics of the parametric results kept by the retest policy
+    fn test_stats_rows(&self, file_name: &str, keep: &[bool]) -> Vec<StatsRow> {
+        let prrs = self.file_prrs(file_name);
+        let file_ptrs = &self.ptr_data[file_name];
+        let file_pfs = &self.pf_data[file_name];
+
+        let mut tests: Vec<(&ColumnName, &TestHeader)> = file_ptrs
+            .keys()
+            .filter_map(|tname| Some((tname, self.header_cols.get(file_name)?.get(tname)?)))
+            .collect();
+        tests.sort_by(|a, b| (a.1.test_num, &a.1.test_txt).cmp(&(b.1.test_num, &b.1.test_txt)));
+
+        let groups: Vec<Option<(HeadNum, SiteNum)>> = if self.options.stats_by_site {
+            let mut sites: Vec<(HeadNum, SiteNum)> = prrs
+                .iter()
+                .map(|prr| (prr.head_num, prr.site_num))
+                .collect();
+            sites.sort();
+            sites.dedup();
+            sites.into_iter().map(Some).collect()
+        } else {
+            vec![None]
+        };
+
+        let mut rows: Vec<StatsRow> = vec![];
+        for (tname, header) in tests {
+            let results = &file_ptrs[tname];
+            let pfs =
+                file_pfs.get(&[("PF").to_string(), tname.clone()].join(&self.options.separator));
+
+            for group in &groups {
+                let mut values: Vec<f64> = vec![];
+                let mut fail_count = 0;
+
+                for (i, prr) in prrs.iter().enumerate() {
+                    if !keep[i] || group.is_some_and(|x| x != (prr.head_num, prr.site_num)) {
+                        continue;
+                    }
+                    if let Some(Some(result)) = results.get(i) {
+                        values.push(*result as f64);
+                    }
+                    if let Some(Some(0)) = pfs.and_then(|x| x.get(i)) {
+                        fail_count += 1;
+                    }
+                }
+
+                // skip sites which never ran the test
+                if values.is_empty() && group.is_some() {
+                    continue;
+                }
+
+                let stats = test_stats(&mut values, header, fail_count);
+                rows.push((*group, header.clone(), stats));
+            }
+        }
+
+        rows
+    }
+
+    // limits in force of each test, one row per run of parts sharing them
+    fn limit_ranges(&mut self, file_name: &str) -> Vec<LimitRange> {
+        let mut logs: Vec<((ColumnName, LimitScope), LimitLog)> = self
+            .limit_log
+            .remove(file_name)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        logs.sort_by(|a, b| {
+            let key = |x: &((ColumnName, LimitScope), LimitLog)| {
+                let header = &x.1[0].1;
+                (x.0 .1, header.test_num, header.test_txt.clone())
+            };
+            key(a).cmp(&key(b))
+        });
+
+        let prrs = self.file_prrs(file_name);
+        let mut rows: Vec<LimitRange> = vec![];
+        for ((_, site), log) in logs {
+            for (parts, header) in log {
+                rows.push(LimitRange {
+                    site,
+                    header,
+                    first_part_id: prrs[parts[0]].part_id.clone(),
+                    last_part_id: prrs[*parts.last().unwrap()].part_id.clone(),
+                    n_parts: parts.len() as u32,
+                });
+            }
+        }
+
+        rows
+    }
+
+    // TSR test synopsis, reconciled against every test of every part
+    fn tsr_rows(&mut self, file_name: &str) -> Vec<(rust_stdf::TSR, TsrComputed, String)> {
+        let tsrs = self.tsr_cols.remove(file_name).unwrap_or_default();
+
+        let options = &self.options;
+        let prrs = self.file_prrs(file_name);
+        let file_headers = self.header_cols.get(file_name);
+        let file_ptrs = &self.ptr_data[file_name];
+        let file_ftrs = &self.ftr_data[file_name];
+        let file_pfs = &self.pf_data[file_name];
+
+        tsrs.into_iter()
+            .map(|mut tsr| {
+                // the pins of a MPR share its test number and are summarised together
+                let columns: Vec<(&ColumnName, &TestHeader)> = file_headers
+                    .into_iter()
+                    .flatten()
+                    .filter(|(tname, header)| {
+                        header.test_num == tsr.test_num
+                            && (file_ptrs.contains_key(*tname) || file_ftrs.contains_key(*tname))
+                    })
+                    .collect();
+
+                // head 255 = all heads, site 255 = all sites of the head
+                let parts = prrs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, prr)| tsr.head_num == 255 || prr.head_num == tsr.head_num)
+                    .filter(|(_, prr)| {
+                        tsr.head_num == 255 || tsr.site_num == 255 || prr.site_num == tsr.site_num
+                    })
+                    .map(|(i, _)| i);
+
+                let mut computed = TsrComputed::default();
+                let mut values: Vec<f64> = vec![];
+
+                for i in parts {
+                    let mut executed = false;
+                    let mut failed = false;
+
+                    for (tname, _) in &columns {
+                        if let Some(Some(result)) = file_ptrs.get(*tname).and_then(|x| x.get(i)) {
+                            values.push(*result as f64);
+                            executed = true;
+                        }
+                        if let Some(Some(_)) = file_ftrs.get(*tname).and_then(|x| x.get(i)) {
+                            executed = true;
+                        }
+
+                        let pf_key =
+                            [("PF").to_string(), tname.to_string()].join(&options.separator);
+                        if let Some(Some(0)) = file_pfs.get(&pf_key).and_then(|x| x.get(i)) {
+                            failed = true;
+                        }
+                    }
+
+                    computed.exec_cnt += executed as u32;
+                    computed.fail_cnt += failed as u32;
+                }
+
+                if !values.is_empty() {
+                    computed.test_min = values.iter().copied().reduce(f64::min);
+                    computed.test_max = values.iter().copied().reduce(f64::max);
+                    computed.tst_sums = Some(values.iter().sum());
+                    computed.tst_sqrs = Some(values.iter().map(|x| x * x).sum());
+                }
+
+                // compare in the units of the reported results
+                if options.scaled_results {
+                    let res_scal = columns.first().and_then(|(_, header)| header.res_scal);
+                    tsr.test_min = scale_value(tsr.test_min, res_scal);
+                    tsr.test_max = scale_value(tsr.test_max, res_scal);
+                    tsr.tst_sums = scale_value(tsr.tst_sums, res_scal);
+                    tsr.tst_sqrs = scale_value(scale_value(tsr.tst_sqrs, res_scal), res_scal);
+                }
+
+                let mismatches = tsr_mismatches(&tsr, &computed);
+                (tsr, computed, mismatches)
+            })
+            .collect()
+    }
+
+    // bin summary, comparing the HBR/SBR counts with the PRRs
+    fn bin_rows(&mut self, file_name: &str, lot_id: &str) -> Vec<BinRow> {
+        let records = self.bin_records.remove(file_name).unwrap_or_default();
+        let prrs = self.file_prrs(file_name);
+
+        // head 255 = all heads, site 255 = all sites
+        let prr_count = |bin_type: &str, bin_num: BinNum, head_num, site_num| {
+            prrs.iter()
+                .filter(|prr| head_num == 255 || prr.head_num == head_num)
+                .filter(|prr| head_num == 255 || site_num == 255 || prr.site_num == site_num)
+                .filter(|prr| match bin_type {
+                    "HBIN" => prr.hard_bin == bin_num,
+                    _ => prr.soft_bin == bin_num,
+                })
+                .count() as u32
+        };
+
+        let mut rows: Vec<BinRow> = records
+            .iter()
+            .map(|x| BinRow {
+                lot_id: lot_id.to_string(),
+                bin_type: x.bin_type,
+                bin_num: x.bin_num,
+                bin_nam: x.bin_nam.clone(),
+                bin_pf: x.bin_pf,
+                head_num: x.head_num,
+                // site is ignored when the record summarises all heads
+                site_num: if x.head_num == 255 { 255 } else { x.site_num },
+                summary_cnt: Some(x.bin_cnt),
+                prr_cnt: prr_count(x.bin_type, x.bin_num, x.head_num, x.site_num),
+            })
+            .collect();
+
+        // bins assigned to parts without any HBR/SBR
+        let mut missing: Vec<(&str, BinNum)> = prrs
+            .iter()
+            .flat_map(|prr| [("HBIN", prr.hard_bin), ("SBIN", prr.soft_bin)])
+            .filter(|(bin_type, bin_num)| {
+                !records
+                    .iter()
+                    .any(|x| x.bin_type == *bin_type && x.bin_num == *bin_num)
+            })
+            .collect();
+        missing.sort();
+        missing.dedup();
+
+        rows.extend(missing.into_iter().map(|(bin_type, bin_num)| BinRow {
+            lot_id: lot_id.to_string(),
+            bin_type,
+            bin_num,
+            bin_nam: "".to_string(),
+            bin_pf: ' ',
+            head_num: 255,
+            site_num: 255,
+            summary_cnt: None,
+            prr_cnt: prr_count(bin_type, bin_num, 255, 255),
+        }));
+
+        rows
+    }
+
+    // test metadata of the limits header, keyed by the report column names
+    fn report_headers(&mut self, file_name: &str) -> HashMap<ColumnName, TestHeader> {
+        self.header_cols
+            .remove(file_name)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(tname, header)| (self.display_name(file_name, &tname), header))
+            .collect()
+    }
+
+    fn file_status(&mut self, file_name: &str) -> FileStatus {
+        match self.read_errors.remove(file_name) {
+            Some(err) => FileStatus::Incomplete(err),
+            None if !self.mir_cols.contains_key(file_name) => {
+                FileStatus::Incomplete(Error::MissingRecord(file_name.to_string(), "MIR"))
+            }
+            None if !self.sdr_cols.contains_key(file_name) => {
+                FileStatus::Incomplete(Error::MissingRecord(file_name.to_string(), "SDR"))
+            }
+            None => FileStatus::Ok,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits_of(
+        limits: &mut HashMap<LimitScope, TestLimits>,
+        site: (HeadNum, SiteNum),
+    ) -> (Option<f32>, Option<f32>) {
+        effective_limits(site_limits(limits, site, &"100::vdd".to_string()))
+    }
+
+    #[test]
+    fn sites_reuse_default_limits() {
+        let options = Options::default();
+        let test_key = "100::vdd".to_string();
+        let mut file_limits = HashMap::new();
+        add_limits(
+            &mut file_limits,
+            (1, 0),
+            &test_key,
+            PtrOptionalData::with_limits(0b0000_1110, 0.5, 1.5),
+            &options,
+        );
+        // bit 4/5 set = reuse the default limits
+        add_limits(
+            &mut file_limits,
+            (1, 1),
+            &test_key,
+            PtrOptionalData::with_limits(0b0011_1110, 0.0, 0.0),
+            &options,
+        );
+
+        assert_eq!(limits_of(&mut file_limits, (1, 0)), (Some(0.5), Some(1.5)));
+        assert_eq!(limits_of(&mut file_limits, (1, 1)), (Some(0.5), Some(1.5)));
+        assert_eq!(limits_of(&mut file_limits, (1, 2)), (Some(0.5), Some(1.5)));
+    }
+
+    #[test]
+    fn first_limits_are_kept_across_sites() {
+        let options = Options::default();
+        let test_key = "100::vdd".to_string();
+        let mut file_limits = HashMap::new();
+        add_limits(
+            &mut file_limits,
+            (1, 0),
+            &test_key,
+            PtrOptionalData::with_limits(0b0000_1110, 0.5, 1.5),
+            &options,
+        );
+        add_limits(
+            &mut file_limits,
+            (1, 1),
+            &test_key,
+            PtrOptionalData::with_limits(0b0000_1110, 0.6, 1.4),
+            &options,
+        );
+
+        assert_eq!(limits_of(&mut file_limits, (1, 1)), (Some(0.5), Some(1.5)));
+    }
+
+    #[test]
+    fn per_site_limits_keep_the_limits_of_each_site() {
+        let options = Options {
+            per_site_limits: true,
+            ..Options::default()
+        };
+        let test_key = "100::vdd".to_string();
+        let mut file_limits = HashMap::new();
+        add_limits(
+            &mut file_limits,
+            (1, 0),
+            &test_key,
+            PtrOptionalData::with_limits(0b0000_1110, 0.5, 1.5),
+            &options,
+        );
+        add_limits(
+            &mut file_limits,
+            (1, 1),
+            &test_key,
+            PtrOptionalData::with_limits(0b0000_1110, 0.6, 1.4),
+            &options,
+        );
+        add_limits(
+            &mut file_limits,
+            (1, 2),
+            &test_key,
+            PtrOptionalData::with_limits(0b0011_1110, 0.0, 0.0),
+            &options,
+        );
+
+        assert_eq!(limits_of(&mut file_limits, (1, 0)), (Some(0.5), Some(1.5)));
+        assert_eq!(limits_of(&mut file_limits, (1, 1)), (Some(0.6), Some(1.4)));
+        // a site reusing the default limits takes the limits shared by every site
+        assert_eq!(limits_of(&mut file_limits, (1, 2)), (Some(0.5), Some(1.5)));
+    }
+
+    #[test]
+    fn default_low_and_high_limits_are_reused_separately() {
+        let test_key = "100::vdd".to_string();
+        for (per_site_limits, dynamic_limits) in [(true, false), (false, true), (true, true)] {
+            let options = Options {
+                per_site_limits,
+                dynamic_limits,
+                ..Options::default()
+            };
+            let mut file_limits = HashMap::new();
+            add_limits(
+                &mut file_limits,
+                (1, 0),
+                &test_key,
+                PtrOptionalData::with_limits(0b0000_1110, 0.5, 1.5),
+                &options,
+            );
+            // bit 4 set = the default low limit with a new high limit
+            add_limits(
+                &mut file_limits,
+                (1, 1),
+                &test_key,
+                PtrOptionalData::with_limits(0b0001_1110, 0.0, 1.2),
+                &options,
+            );
+            assert_eq!(limits_of(&mut file_limits, (1, 1)), (Some(0.5), Some(1.2)));
+
+            // bit 5 set = a new low limit with the default high limit
+            add_limits(
+                &mut file_limits,
+                (1, 1),
+                &test_key,
+                PtrOptionalData::with_limits(0b0010_1110, 0.7, 0.0),
+                &options,
+            );
+            let expected = if dynamic_limits {
+                (Some(0.7), Some(1.2))
+            } else {
+                (Some(0.5), Some(1.2))
+            };
+            assert_eq!(limits_of(&mut file_limits, (1, 1)), expected);
+        }
+    }
+
+    fn prr(part_id: &str, x_coord: i16, y_coord: i16, part_flg: u8) -> rust_stdf::PRR {
+        rust_stdf::PRR {
+            part_flg: [part_flg],
+            x_coord,
+            y_coord,
+            part_id: part_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn retests_are_grouped_by_part_id() {
+        let prrs = [
+            prr("1", 0, 0, 0),
+            prr("2", 1, 0, 0),
+            prr("1", 5, 5, 0b01),
+            prr("1", 6, 6, 0b01),
+        ];
+        let wafer_ids = vec![None; prrs.len()];
+        assert_eq!(
+            retest_groups(prrs.iter().zip(&wafer_ids)),
+            [(0, 0), (1, 0), (0, 1), (0, 2)]
+        );
+    }
+
+    #[test]
+    fn retests_are_grouped_by_xy() {
+        let prrs = [prr("1", 0, 0, 0), prr("2", 1, 0, 0), prr("3", 1, 0, 0b10)];
+        let wafer_ids = vec![None; prrs.len()];
+        assert_eq!(
+            retest_groups(prrs.iter().zip(&wafer_ids)),
+            [(0, 0), (1, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn parts_without_retest_flags_are_new_parts() {
+        // the same part ID or X/Y is a new part unless PART_FLG says it is a retest
+        let prrs = [prr("1", 0, 0, 0), prr("1", 0, 0, 0), prr("2", 0, 0, 0b01)];
+        let wafer_ids = vec![None; prrs.len()];
+        assert_eq!(
+            retest_groups(prrs.iter().zip(&wafer_ids)),
+            [(0, 0), (1, 0), (2, 0)]
+        );
+    }
+
+    #[test]
+    fn retests_are_grouped_within_a_wafer() {
+        let prrs = [
+            prr("1", 0, 0, 0),
+            prr("1", 0, 0, 0b01),
+            prr("1", 0, 0, 0b10),
+        ];
+        let wafer_ids = vec![
+            Some("W1".to_string()),
+            Some("W2".to_string()),
+            Some("W1".to_string()),
+        ];
+        assert_eq!(
+            retest_groups(prrs.iter().zip(&wafer_ids)),
+            [(0, 0), (1, 0), (0, 1)]
+        );
+    }
+
+    fn sequence(tests: &[(u32, &str)]) -> Vec<(u32, ColumnName)> {
+        tests.iter().map(|(n, x)| (*n, x.to_string())).collect()
+    }
+
+    #[test]
+    fn columns_first_seen_mid_flow_keep_their_place() {
+        let mut order = ColumnOrder::default();
+        order.merge(&sequence(&[(100, "a"), (300, "c"), (400, "d")]));
+        // a conditional test runs for a later part only
+        order.merge(&sequence(&[(100, "a"), (300, "c"), (200, "b"), (400, "d")]));
+        order.merge(&sequence(&[(50, "x"), (100, "a"), (400, "d"), (500, "e")]));
+        // a retest which only runs a later test
+        order.merge(&sequence(&[(600, "f")]));
+
+        assert_eq!(
+            order.ordered(TestOrder::Execution),
+            sequence(&[
+                (50, "x"),
+                (100, "a"),
+                (300, "c"),
+                (200, "b"),
+                (400, "d"),
+                (500, "e"),
+                (600, "f"),
+            ])
+        );
+        assert_eq!(
+            order.ordered(TestOrder::TestNumber),
+            sequence(&[
+                (50, "x"),
+                (100, "a"),
+                (200, "b"),
+                (300, "c"),
+                (400, "d"),
+                (500, "e"),
+                (600, "f"),
+            ])
+        );
+    }
+
+    fn ptr(test_num: u32, site_num: u8, result: f32, hi_limit: f32) -> StdfRecord {
+        StdfRecord::PTR(rust_stdf::PTR {
+            test_num,
+            head_num: 1,
+            site_num,
+            result,
+            test_txt: format!("t{}", test_num),
+            opt_flag: Some([0b0000_1110]),
+            lo_limit: Some(0.0),
+            hi_limit: Some(hi_limit),
+            ..Default::default()
+        })
+    }
+
+    fn part(site_num: u8, part_id: &str) -> StdfRecord {
+        StdfRecord::PRR(rust_stdf::PRR {
+            head_num: 1,
+            site_num,
+            part_id: part_id.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn results_are_checked_against_the_limits_of_their_record() {
+        let mut builder = ParametricBuilder::new(Options {
+            pass_fail_columns: true,
+            dynamic_limits: true,
+            ..Options::default()
+        });
+        // site 1 changes the limits before the part of site 0 ends
+        builder.add_records(
+            "dynamic.stdf",
+            [
+                ptr(1, 0, 1.5, 2.0),
+                ptr(1, 1, 1.5, 1.0),
+                part(0, "1"),
+                part(1, "2"),
+            ],
+        );
+        let report = builder.build().unwrap();
+
+        let pass_fail: Vec<Option<u32>> = report.files[0]
+            .parametric
+            .column("PF::1::t1")
+            .unwrap()
+            .u32()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(pass_fail, [Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn limit_changes_count_the_parts_which_ran_the_test() {
+        let mut builder = ParametricBuilder::new(Options {
+            dynamic_limits: true,
+            ..Options::default()
+        });
+        builder.add_records(
+            "dynamic.stdf",
+            [
+                ptr(1, 0, 1.0, 2.0),
+                part(0, "1"),
+                ptr(2, 0, 1.0, 2.0),
+                part(0, "2"),
+                ptr(1, 0, 1.0, 3.0),
+                part(0, "3"),
+            ],
+        );
+        let report = builder.build().unwrap();
+        let limit_changes = report.files[0].limit_changes.as_ref().unwrap();
+
+        let column = |name: &str| -> Vec<String> {
+            let values = limit_changes
+                .column(name)
+                .unwrap()
+                .cast(&DataType::Utf8)
+                .unwrap();
+            values
+                .utf8()
+                .unwrap()
+                .into_no_null_iter()
+                .map(String::from)
+                .collect()
+        };
+        assert_eq!(column("Test Num"), ["1", "1", "2"]);
+        assert_eq!(column("First Part ID"), ["1", "3", "2"]);
+        assert_eq!(column("Last Part ID"), ["1", "3", "2"]);
+        assert_eq!(column("Parts"), ["1", "1", "1"]);
+    }
+}
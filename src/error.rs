@@ -0,0 +1,63 @@
+use polars::prelude::PolarsError;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The STDF file could not be opened or read
+    Io(String, std::io::Error),
+    /// A record could not be parsed, the file is read up to this record
+    MalformedRecord(String, String),
+    /// A record the reports depend on is not in the file
+    MissingRecord(String, &'static str),
+    /// The STDF file has no records
+    EmptyFile(String),
+    /// A report could not be written
+    Output(PathBuf, PolarsError),
+    /// A report could not be built
+    Polars(PolarsError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(file_name, err) => write!(f, "{} :: cannot read file :: {}", file_name, err),
+            Error::MalformedRecord(file_name, err) => {
+                write!(f, "{} :: malformed record :: {}", file_name, err)
+            }
+            Error::MissingRecord(file_name, rec_type) => {
+                write!(f, "{} :: no {} record in file", file_name, rec_type)
+            }
+            Error::EmptyFile(file_name) => write!(f, "{} :: empty file", file_name),
+            Error::Output(path, err) => {
+                write!(f, "{} :: cannot write report :: {}", path.display(), err)
+            }
+            Error::Polars(err) => write!(f, "cannot build report :: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<PolarsError> for Error {
+    fn from(err: PolarsError) -> Self {
+        Error::Polars(err)
+    }
+}
+
+/// Outcome of reading and reporting one STDF file
+#[derive(Debug)]
+pub enum FileStatus {
+    Ok,
+    /// Reported, but records are missing or reading stopped early
+    Incomplete(Error),
+    /// Nothing is reported for the file
+    Failed(Error),
+}
+
+impl FileStatus {
+    /// Whether any data of the file is in the reports
+    pub fn is_reported(&self) -> bool {
+        !matches!(self, FileStatus::Failed(_))
+    }
+}
@@ -0,0 +1,45 @@
+//! Build parametric, bin, yield and test reports as polars `DataFrame`s
+//! from STDF files.
+//!
+//! ```no_run
+//! use rapid::{Options, ParametricBuilder};
+//!
+//! let mut builder = ParametricBuilder::new(Options {
+//!     pass_fail_columns: true,
+//!     ..Options::default()
+//! });
+//! builder.add_files(&["lot1.stdf".to_string()]);
+//!
+//! let report = builder.build().unwrap();
+//! let df = report.parametric().unwrap();
+//! ```
+
+mod builder;
+mod error;
+mod limits;
+mod options;
+mod output;
+mod pins;
+mod reader;
+mod report;
+mod scan;
+mod summary;
+
+pub use builder::ParametricBuilder;
+pub use error::{Error, FileStatus};
+pub use limits::{with_limits_header, TestHeader};
+pub use options::{Options, RetestPolicy, TestOrder};
+pub use output::{combined_report_path, file_report_path, write_df, OutputFormat};
+pub use reader::open_stdf;
+pub use report::{FileReport, Report};
+
+type HeadNum = u8;
+type SiteNum = u8;
+type BinNum = u16;
+type BinDescription = String;
+type FileName = String;
+type PartId = usize;
+type ColumnName = String;
+type TestResult = Option<f32>;
+type FunctionalResult = Option<u32>;
+type PinIndex = u16;
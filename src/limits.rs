@@ -0,0 +1,275 @@
+use crate::ColumnName;
+use polars::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub(crate) struct PtrOptionalData {
+    pub(crate) opt_flag: Option<[u8; 1]>, // Optional data flag
+    pub(crate) res_scal: Option<i8>,      // Test results scaling exponent
+    pub(crate) llm_scal: Option<i8>,      // Low limit scaling exponent
+    pub(crate) hlm_scal: Option<i8>,      // High limit scaling exponent
+    pub(crate) lo_limit: Option<f32>,     // Low test limit value
+    pub(crate) hi_limit: Option<f32>,     // High test limit value
+    pub(crate) units: Option<String>,     // Test units
+    pub(crate) _c_resfmt: Option<String>, // ANSI C result format string
+    pub(crate) _c_llmfmt: Option<String>, // ANSI C low limit format string
+    pub(crate) _c_hlmfmt: Option<String>, // ANSI C high limit format string
+    pub(crate) lo_spec: Option<f32>,      // Low specification limit value
+    pub(crate) hi_spec: Option<f32>,      // High specification limit value
+}
+
+#[cfg(test)]
+impl PtrOptionalData {
+    // limits of a test record with the given optional data flag, for unit tests
+    pub(crate) fn with_limits(opt_flag: u8, lo_limit: f32, hi_limit: f32) -> Self {
+        PtrOptionalData {
+            opt_flag: Some([opt_flag]),
+            res_scal: Some(0),
+            llm_scal: Some(0),
+            hlm_scal: Some(0),
+            lo_limit: Some(lo_limit),
+            hi_limit: Some(hi_limit),
+            units: Some("V".to_string()),
+            _c_resfmt: None,
+            _c_llmfmt: None,
+            _c_hlmfmt: None,
+            lo_spec: None,
+            hi_spec: None,
+        }
+    }
+}
+
+pub(crate) type TestLimits = HashMap<ColumnName, PtrOptionalData>;
+
+/// Test metadata of a report column, written in the limits header block
+#[derive(Debug, Clone)]
+pub struct TestHeader {
+    pub test_num: u32,
+    pub test_txt: String,
+    pub units: Option<String>,
+    pub lo_limit: Option<f32>,
+    pub hi_limit: Option<f32>,
+    pub lo_spec: Option<f32>,
+    pub hi_spec: Option<f32>,
+    pub res_scal: Option<i8>,
+}
+
+pub(crate) fn effective_limits(limits: &PtrOptionalData) -> (Option<f32>, Option<f32>) {
+    let lo_limit =
+        if limits.opt_flag.is_some() && ((limits.opt_flag.unwrap()[0] & 0b0101_0000) == 0) {
+            limits.lo_limit
+        } else {
+            None
+        };
+    let hi_limit =
+        if limits.opt_flag.is_some() && ((limits.opt_flag.unwrap()[0] & 0b1010_0000) == 0) {
+            limits.hi_limit
+        } else {
+            None
+        };
+
+    (lo_limit, hi_limit)
+}
+
+// PARM_FLG bit 6/7 set = a result equal to the low/high limit passes
+pub(crate) fn is_within_limits(result: f32, limits: &PtrOptionalData, parm_flg: u8) -> bool {
+    let (lo_limit, hi_limit) = effective_limits(limits);
+    let is_lo_inclusive = parm_flg & 0b0100_0000 != 0;
+    let is_hi_inclusive = parm_flg & 0b1000_0000 != 0;
+
+    let pass_lo_limit = match lo_limit {
+        Some(lo_limit) => result > lo_limit || (is_lo_inclusive && result == lo_limit),
+        None => true,
+    };
+    let pass_hi_limit = match hi_limit {
+        Some(hi_limit) => result < hi_limit || (is_hi_inclusive && result == hi_limit),
+        None => true,
+    };
+
+    pass_lo_limit && pass_hi_limit
+}
+
+// TEST_FLG bit 1 set = result invalid, bit 4 set = test not executed
+pub(crate) fn is_valid_result(test_flg: u8) -> bool {
+    test_flg & 0b0001_0010 == 0
+}
+
+// the tester's own verdict, bit 6 set = no pass/fail indication,
+// otherwise bit 7 set = test failed
+pub(crate) fn tester_pass_fail(test_flg: u8) -> Option<bool> {
+    (test_flg & 0b0100_0000 == 0).then_some(test_flg & 0b1000_0000 == 0)
+}
+
+// SI prefix for a STDF scaling exponent, `None` for exponents
+// which have no common prefix
+fn unit_prefix(scal: i8) -> Option<&'static str> {
+    match scal {
+        15 => Some("f"),
+        12 => Some("p"),
+        9 => Some("n"),
+        6 => Some("u"),
+        3 => Some("m"),
+        2 => Some("%"),
+        0 => Some(""),
+        -3 => Some("K"),
+        -6 => Some("M"),
+        -9 => Some("G"),
+        -12 => Some("T"),
+        _ => None,
+    }
+}
+
+pub(crate) fn scale_value(value: f32, scal: Option<i8>) -> f32 {
+    match scal.filter(|x| unit_prefix(*x).is_some()) {
+        Some(scal) => (value as f64 * 10f64.powi(scal as i32)) as f32,
+        None => value,
+    }
+}
+
+pub(crate) fn scaled_units(units: &Option<String>, scal: Option<i8>) -> Option<String> {
+    let prefix = scal.and_then(unit_prefix).unwrap_or("");
+    match units {
+        Some(units) if !units.is_empty() || !prefix.is_empty() => Some([prefix, units].concat()),
+        _ => None,
+    }
+}
+
+pub(crate) fn test_header(
+    test_num: u32,
+    test_txt: String,
+    limits: &PtrOptionalData,
+    is_scaled: bool,
+) -> TestHeader {
+    let (lo_limit, hi_limit) = effective_limits(limits);
+
+    // bit 2/3 set = no low/high specification limit
+    let opt_flag = limits.opt_flag.map(|x| x[0]).unwrap_or(0b0000_1100);
+    let lo_spec = limits.lo_spec.filter(|_| opt_flag & 0b0000_0100 == 0);
+    let hi_spec = limits.hi_spec.filter(|_| opt_flag & 0b0000_1000 == 0);
+
+    // limits and specs take the result scaling so every value of a test
+    // shares the units of its results
+    if is_scaled {
+        TestHeader {
+            test_num,
+            test_txt,
+            units: scaled_units(&limits.units, limits.res_scal),
+            lo_limit: lo_limit.map(|x| scale_value(x, limits.res_scal)),
+            hi_limit: hi_limit.map(|x| scale_value(x, limits.res_scal)),
+            lo_spec: lo_spec.map(|x| scale_value(x, limits.res_scal)),
+            hi_spec: hi_spec.map(|x| scale_value(x, limits.res_scal)),
+            res_scal: limits.res_scal,
+        }
+    } else {
+        TestHeader {
+            test_num,
+            test_txt,
+            units: limits.units.clone(),
+            lo_limit,
+            hi_limit,
+            lo_spec,
+            hi_spec,
+            res_scal: limits.res_scal,
+        }
+    }
+}
+
+/// Stack the test number, name, units, limits and specs of each column
+/// above the data, labelling the rows in the first column
+pub fn with_limits_header(
+    df: &DataFrame,
+    headers: &HashMap<ColumnName, TestHeader>,
+) -> PolarsResult<DataFrame> {
+    let labels = [
+        "Test Num",
+        "Test Name",
+        "Units",
+        "Lo Limit",
+        "Hi Limit",
+        "Lo Spec",
+        "Hi Spec",
+    ];
+    let fmt = |x: Option<f32>| x.map(|x| x.to_string());
+
+    let header_fields: Vec<Series> = df
+        .get_columns()
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let values: Vec<Option<String>> = match headers.get(s.name()) {
+                Some(h) => vec![
+                    Some(h.test_num.to_string()),
+                    Some(h.test_txt.clone()),
+                    h.units.clone(),
+                    fmt(h.lo_limit),
+                    fmt(h.hi_limit),
+                    fmt(h.lo_spec),
+                    fmt(h.hi_spec),
+                ],
+                None if i == 0 => labels.iter().map(|x| Some(x.to_string())).collect(),
+                None => vec![None; labels.len()],
+            };
+            Series::new(s.name(), values)
+        })
+        .collect();
+
+    let data_fields = df
+        .get_columns()
+        .iter()
+        .map(|s| s.cast(&DataType::Utf8))
+        .collect::<PolarsResult<Vec<Series>>>()?;
+
+    let mut header = DataFrame::new(header_fields)?;
+    header.vstack_mut(&DataFrame::new(data_fields)?)?;
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_are_exclusive_without_parm_flg() {
+        let limits = PtrOptionalData::with_limits(0b0000_1110, 0.5, 1.5);
+        assert!(is_within_limits(1.0, &limits, 0));
+        assert!(!is_within_limits(0.5, &limits, 0));
+        assert!(!is_within_limits(1.5, &limits, 0));
+    }
+
+    #[test]
+    fn parm_flg_makes_each_limit_inclusive() {
+        let limits = PtrOptionalData::with_limits(0b0000_1110, 0.5, 1.5);
+        assert!(is_within_limits(0.5, &limits, 0b0100_0000));
+        assert!(!is_within_limits(1.5, &limits, 0b0100_0000));
+        assert!(!is_within_limits(0.5, &limits, 0b1000_0000));
+        assert!(is_within_limits(1.5, &limits, 0b1000_0000));
+        assert!(!is_within_limits(0.4, &limits, 0b1100_0000));
+        assert!(!is_within_limits(1.6, &limits, 0b1100_0000));
+    }
+
+    #[test]
+    fn opt_flag_drops_limits() {
+        let mut limits = PtrOptionalData::with_limits(0b0000_1110, 0.5, 1.5);
+        limits.opt_flag = Some([0b1100_1110]);
+        assert!(is_within_limits(0.0, &limits, 0));
+        assert!(is_within_limits(2.0, &limits, 0));
+    }
+
+    #[test]
+    fn invalid_and_not_executed_results_are_dropped() {
+        assert!(is_valid_result(0));
+        assert!(!is_valid_result(0b0000_0010));
+        assert!(!is_valid_result(0b0001_0000));
+        // alarm, unreliable and failed results are still results
+        assert!(is_valid_result(0b1000_0101));
+    }
+
+    #[test]
+    fn tester_pass_fail_reads_bits_6_and_7() {
+        assert_eq!(tester_pass_fail(0), Some(true));
+        assert_eq!(tester_pass_fail(0b1000_0000), Some(false));
+        assert_eq!(tester_pass_fail(0b0100_0000), None);
+        assert_eq!(tester_pass_fail(0b1100_0000), None);
+        assert_eq!(tester_pass_fail(0b0001_0001), Some(true));
+    }
+}
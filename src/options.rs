@@ -0,0 +1,91 @@
+use clap::ValueEnum;
+
+/// Which reports are built and how the parametric report is laid out
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Name separator for test names and numbers
+    pub separator: String,
+    /// Include pass/fail column for each test in parametric report
+    pub pass_fail_columns: bool,
+    /// Include the tester's pass/fail from TEST_FLG after each pass/fail column,
+    /// reporting parts where it differs from the limits
+    pub tester_pass_fail: bool,
+    /// Include functional tests in parametric report
+    pub functional_tests: bool,
+    /// Scale parametric results by RES_SCAL and add units to test column names
+    pub scaled_results: bool,
+    /// Write test number, name, units, limits and specs as rows above the parametric data
+    pub limits_header: bool,
+    /// Output one row per part and test instead of one column per test
+    pub long_format: bool,
+    /// Keep the limits of each head and site instead of sharing the first limits of a test
+    pub per_site_limits: bool,
+    /// Evaluate pass/fail against the latest limits of a test and build a report of
+    /// the limits in force over each run of parts
+    pub dynamic_limits: bool,
+    /// Which test of a retested part to report
+    pub retest: RetestPolicy,
+    /// Build bin summary reports per file and per lot from HBR/SBR records and PRR counts
+    pub bin_summary: bool,
+    /// Build a yield report by lot, file, head and site
+    pub yield_summary: bool,
+    /// Build count, mean, std dev, percentiles, Cp/Cpk and fail rate of each parametric test
+    pub test_stats: bool,
+    /// Break the test statistics down by head and site
+    pub stats_by_site: bool,
+    /// Build the TSR test synopsis records, reconciled against the individual test results
+    pub test_summary: bool,
+    /// Build a report of every failing FTR with its vector, cycle count and pins
+    pub ftr_fails: bool,
+    /// Build a pin map of the PMR pins and PGR pin groups of each file
+    pub pin_map: bool,
+    /// Build a report of the fails logged by V4-2007 STR scan records, with
+    /// the pattern, chain and cell names of the PSR, CDR and CNR records
+    pub scan_fails: bool,
+    /// Order of the test columns in the parametric report
+    pub test_order: TestOrder,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            separator: "::".to_string(),
+            pass_fail_columns: false,
+            tester_pass_fail: false,
+            functional_tests: false,
+            scaled_results: false,
+            limits_header: false,
+            long_format: false,
+            per_site_limits: false,
+            dynamic_limits: false,
+            retest: RetestPolicy::All,
+            bin_summary: false,
+            yield_summary: false,
+            test_stats: false,
+            stats_by_site: false,
+            test_summary: false,
+            ftr_fails: false,
+            pin_map: false,
+            scan_fails: false,
+            test_order: TestOrder::Execution,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum RetestPolicy {
+    /// Keep every test of a part, numbered by a Retest Index column
+    All,
+    /// Keep only the first test of a part
+    First,
+    /// Keep only the final retest of a part
+    Last,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TestOrder {
+    /// Order tests as they were first executed in the flow
+    Execution,
+    /// Order tests by test number, then by execution order
+    TestNumber,
+}
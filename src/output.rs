@@ -0,0 +1,75 @@
+use crate::error::Error;
+use clap::ValueEnum;
+use polars::prelude::*;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Csv,
+    Ipc,
+    Parquet,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Ipc => "ipc",
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// Write a report as CSV, or as ZSTD compressed Arrow IPC or Parquet
+pub fn write_df(df: &mut DataFrame, path: &Path, format: OutputFormat) -> Result<(), Error> {
+    let output_error = |e| Error::Output(path.to_path_buf(), e);
+    let mut file = std::fs::File::create(path).map_err(|e| output_error(e.into()))?;
+
+    match format {
+        OutputFormat::Csv => CsvWriter::new(&mut file).finish(df),
+        OutputFormat::Ipc => IpcWriter::new(&mut file)
+            .with_compression(Some(IpcCompression::ZSTD))
+            .finish(df),
+        OutputFormat::Parquet => ParquetWriter::new(&mut file)
+            .with_compression(ParquetCompression::Zstd(None))
+            .finish(df)
+            .map(|_| ()),
+    }
+    .map_err(output_error)
+}
+
+/// Output path of a per-file report, `<stdf file>.<suffix>.<ext>` in the
+/// output directory or next to the STDF file
+pub fn file_report_path(
+    stdf_path: &str,
+    output_dir: &Option<PathBuf>,
+    suffix: &str,
+    format: OutputFormat,
+) -> PathBuf {
+    let path = Path::new(stdf_path);
+
+    let dir = match output_dir {
+        Some(dir) => dir.clone(),
+        None => path.parent().unwrap().to_path_buf(),
+    };
+
+    let extension = [".", suffix, ".", format.extension()].concat();
+    let file_name = [path.file_name().unwrap(), OsStr::new(&extension)].join(OsStr::new(""));
+
+    dir.join(file_name)
+}
+
+/// Output path of a report combining all files, `rapid_<name>.<ext>`
+pub fn combined_report_path(
+    output_dir: &Option<PathBuf>,
+    name: &str,
+    format: OutputFormat,
+) -> PathBuf {
+    let dir = match output_dir {
+        Some(dir) => dir.clone(),
+        None => Path::new(".").to_path_buf(),
+    };
+
+    dir.join(["rapid_", name, ".", format.extension()].concat())
+}
@@ -0,0 +1,102 @@
+use crate::PinIndex;
+use polars::prelude::*;
+use std::collections::BTreeMap;
+
+// pins of a file from its PMR records, and the pin groups of its PGR records
+#[derive(Debug, Default)]
+pub(crate) struct PinMap {
+    pins: BTreeMap<PinIndex, rust_stdf::PMR>,
+    groups: BTreeMap<PinIndex, rust_stdf::PGR>,
+}
+
+impl PinMap {
+    pub(crate) fn add_pmr(&mut self, pmr: rust_stdf::PMR) {
+        self.pins.insert(pmr.pmr_indx, pmr);
+    }
+
+    pub(crate) fn add_pgr(&mut self, pgr: rust_stdf::PGR) {
+        self.groups.insert(pgr.grp_indx, pgr);
+    }
+
+    // name a pin by its PMR logical name, falling back to the channel
+    // name and finally the raw PMR index, and a pin group by its PGR name
+    pub(crate) fn name(&self, index: PinIndex) -> String {
+        match (self.pins.get(&index), self.groups.get(&index)) {
+            (Some(pmr), _) if !pmr.log_nam.is_empty() => pmr.log_nam.clone(),
+            (Some(pmr), _) if !pmr.chan_nam.is_empty() => pmr.chan_nam.clone(),
+            (None, Some(pgr)) if !pgr.grp_nam.is_empty() => pgr.grp_nam.clone(),
+            _ => index.to_string(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pins.is_empty() && self.groups.is_empty()
+    }
+
+    // one row per pin, then one row per pin of each pin group
+    pub(crate) fn pin_map_df(&self, file_name: &str) -> PolarsResult<DataFrame> {
+        let ungrouped = self.pins.keys().map(|index| (None, *index));
+        let grouped = self
+            .groups
+            .values()
+            .flat_map(|pgr| pgr.pmr_indx.iter().map(move |index| (Some(pgr), *index)));
+        let rows: Vec<(Option<&rust_stdf::PGR>, PinIndex)> = ungrouped.chain(grouped).collect();
+
+        let pin = |field: fn(&rust_stdf::PMR) -> String| -> Vec<Option<String>> {
+            rows.iter()
+                .map(|(_, index)| self.pins.get(index).map(field))
+                .collect()
+        };
+
+        DataFrame::new(vec![
+            Series::new("File Name", vec![file_name; rows.len()]),
+            Series::new(
+                "Group Index",
+                rows.iter()
+                    .map(|(pgr, _)| pgr.map(|x| x.grp_indx as u32))
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "Group Name",
+                rows.iter()
+                    .map(|(pgr, _)| pgr.map(|x| x.grp_nam.clone()))
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "Pin Index",
+                rows.iter()
+                    .map(|(_, index)| *index as u32)
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "Channel Type",
+                rows.iter()
+                    .map(|(_, index)| self.pins.get(index).map(|x| x.chan_typ as u32))
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new("Channel Name", pin(|x| x.chan_nam.clone())),
+            Series::new("Physical Name", pin(|x| x.phy_nam.clone())),
+            Series::new("Logical Name", pin(|x| x.log_nam.clone())),
+            Series::new(
+                "Head Num",
+                rows.iter()
+                    .map(|(_, index)| self.pins.get(index).map(|x| x.head_num as u32))
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "Site Num",
+                rows.iter()
+                    .map(|(_, index)| self.pins.get(index).map(|x| x.site_num as u32))
+                    .collect::<Vec<_>>(),
+            ),
+        ])
+    }
+}
+
+// name a pin or pin group of a file, files without a pin map use the raw index
+pub(crate) fn pin_name(pin_map: Option<&PinMap>, index: PinIndex) -> String {
+    match pin_map {
+        Some(pin_map) => pin_map.name(index),
+        None => index.to_string(),
+    }
+}
@@ -0,0 +1,50 @@
+use crate::error::Error;
+use rust_stdf::{stdf_file::*, CompressType};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Open a STDF file, detecting gzip, bzip2 and zip compression from the
+/// magic bytes and falling back to the file extension
+pub fn open_stdf(stdf_path: &str) -> Result<StdfReader<BufReader<File>>, Error> {
+    let io_error = |e| Error::Io(stdf_path.to_string(), e);
+    let mut file = File::open(stdf_path).map_err(io_error)?;
+
+    let mut magic = [0u8; 4];
+    let n_read = file.read(&mut magic).map_err(io_error)?;
+    file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+
+    if n_read == 0 {
+        return Err(Error::EmptyFile(stdf_path.to_string()));
+    }
+
+    let compress_type = match &magic[..n_read] {
+        [0x1f, 0x8b, ..] => CompressType::GzipCompressed,
+        [b'B', b'Z', b'h', ..] => CompressType::BzipCompressed,
+        [b'P', b'K', 0x03, 0x04] => CompressType::ZipCompressed,
+        _ => match Path::new(stdf_path).extension().and_then(OsStr::to_str) {
+            Some("gz") => CompressType::GzipCompressed,
+            Some("bz2") => CompressType::BzipCompressed,
+            Some("zip") => CompressType::ZipCompressed,
+            _ => CompressType::Uncompressed,
+        },
+    };
+
+    // zip archives are read from the first file they contain
+    if let CompressType::ZipCompressed = compress_type {
+        let archive = file.try_clone().map(BufReader::new).map_err(io_error)?;
+        if let Ok(n_files) = zip::ZipArchive::new(archive).map(|x| x.len()) {
+            if n_files > 1 {
+                println!(
+                    "Zip archive holds {} files, reading only the first :: {}",
+                    n_files, stdf_path
+                );
+            }
+        }
+        file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+    }
+
+    StdfReader::from(BufReader::with_capacity(2 << 20, file), &compress_type)
+        .map_err(|e| Error::MalformedRecord(stdf_path.to_string(), e.to_string()))
+}
@@ -0,0 +1,158 @@
+use crate::error::FileStatus;
+use crate::limits::{with_limits_header, TestHeader};
+use crate::ColumnName;
+use polars::functions::diag_concat_df;
+use polars::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Reports built from one STDF file
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub file_name: String,
+    /// One row per part, or one row per part and test in long format
+    pub parametric: DataFrame,
+    /// Test metadata of the parametric columns
+    pub headers: HashMap<ColumnName, TestHeader>,
+    /// Per-wafer summary, for wafer sort files
+    pub wafer: Option<DataFrame>,
+    pub bins: Option<DataFrame>,
+    pub test_stats: Option<DataFrame>,
+    pub test_summary: Option<DataFrame>,
+    /// Limits in force of each test over runs of parts, when limits are dynamic
+    pub limit_changes: Option<DataFrame>,
+    /// One row per failing functional test, with its vector and pins
+    pub ftr_fails: Option<DataFrame>,
+    /// Pins from the PMR records and the pins of each PGR pin group
+    pub pin_map: Option<DataFrame>,
+    /// One row per fail logged by the V4-2007 STR scan records
+    pub scan_fails: Option<DataFrame>,
+    pub(crate) is_limits_header: bool,
+}
+
+impl FileReport {
+    /// The parametric report, below the limits header when requested
+    pub fn parametric_report(&self) -> PolarsResult<DataFrame> {
+        if self.is_limits_header {
+            with_limits_header(&self.parametric, &self.headers)
+        } else {
+            Ok(self.parametric.clone())
+        }
+    }
+}
+
+/// Reports built from every STDF file
+#[derive(Debug)]
+pub struct Report {
+    /// Per-file reports, in the order the files were added
+    pub files: Vec<FileReport>,
+    /// Outcome of every file added, in the order the files were added
+    pub status: Vec<(String, FileStatus)>,
+    /// Yield by lot, file, head and site
+    pub yield_summary: Option<DataFrame>,
+    /// Bin summary of each lot, combining the files of the lot
+    pub lot_bins: Option<DataFrame>,
+    pub(crate) test_columns: Vec<ColumnName>,
+    pub(crate) is_limits_header: bool,
+}
+
+// stack a report of every file, `None` when no file has the report
+fn concat(dfs: Vec<DataFrame>) -> PolarsResult<Option<DataFrame>> {
+    if dfs.is_empty() {
+        return Ok(None);
+    }
+    diag_concat_df(&dfs).map(Some)
+}
+
+impl Report {
+    /// The parametric report of every file, below the limits header when requested
+    pub fn parametric(&self) -> PolarsResult<DataFrame> {
+        let dfs: Vec<DataFrame> = self.files.iter().map(|x| x.parametric.clone()).collect();
+        let df = diag_concat_df(&dfs)?;
+
+        // concatenation appends the columns of later files, so restore the test order
+        let is_test_column: HashSet<&str> = self.test_columns.iter().map(|x| x.as_str()).collect();
+        let column_names: Vec<String> = df
+            .get_column_names()
+            .into_iter()
+            .filter(|x| !is_test_column.contains(x))
+            .map(|x| x.to_string())
+            .chain(self.test_columns.iter().cloned())
+            .collect();
+        let df = df.select(column_names)?;
+
+        if self.is_limits_header {
+            // keep the first header seen for each column
+            let mut headers: HashMap<ColumnName, TestHeader> = HashMap::new();
+            for file in &self.files {
+                for (tname, header) in &file.headers {
+                    headers.entry(tname.clone()).or_insert(header.clone());
+                }
+            }
+            with_limits_header(&df, &headers)
+        } else {
+            Ok(df)
+        }
+    }
+
+    pub fn wafer(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(self.files.iter().filter_map(|x| x.wafer.clone()).collect())
+    }
+
+    pub fn bins(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(self.files.iter().filter_map(|x| x.bins.clone()).collect())
+    }
+
+    pub fn test_stats(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(
+            self.files
+                .iter()
+                .filter_map(|x| x.test_stats.clone())
+                .collect(),
+        )
+    }
+
+    pub fn test_summary(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(
+            self.files
+                .iter()
+                .filter_map(|x| x.test_summary.clone())
+                .collect(),
+        )
+    }
+
+    pub fn limit_changes(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(
+            self.files
+                .iter()
+                .filter_map(|x| x.limit_changes.clone())
+                .collect(),
+        )
+    }
+
+    pub fn ftr_fails(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(
+            self.files
+                .iter()
+                .filter_map(|x| x.ftr_fails.clone())
+                .collect(),
+        )
+    }
+
+    pub fn pin_map(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(
+            self.files
+                .iter()
+                .filter_map(|x| x.pin_map.clone())
+                .collect(),
+        )
+    }
+
+    pub fn scan_fails(&self) -> PolarsResult<Option<DataFrame>> {
+        concat(
+            self.files
+                .iter()
+                .filter_map(|x| x.scan_fails.clone())
+                .collect(),
+        )
+    }
+}
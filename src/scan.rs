@@ -0,0 +1,228 @@
+use crate::pins::{pin_name, PinMap};
+use crate::PinIndex;
+use polars::prelude::*;
+use std::collections::HashMap;
+
+// values of a variable width STR array
+fn uf_values(values: &rust_stdf::KxUf) -> Vec<u64> {
+    match values {
+        rust_stdf::KxUf::F1(x) => x.iter().map(|x| *x as u64).collect(),
+        rust_stdf::KxUf::F2(x) => x.iter().map(|x| *x as u64).collect(),
+        rust_stdf::KxUf::F4(x) => x.iter().map(|x| *x as u64).collect(),
+        rust_stdf::KxUf::F8(x) => x.clone(),
+    }
+}
+
+// patterns of a PSR pattern sequence, continuation records append to the sequence
+#[derive(Debug, Default)]
+struct PatternSequence {
+    name: String,
+    patterns: Vec<(u64, u64, String)>,
+}
+
+// names of the scan patterns, chains, cells and signals of a file,
+// from its V4-2007 PSR, NMR, CNR, SSR and CDR records
+#[derive(Debug, Default)]
+pub(crate) struct ScanMap {
+    sequences: HashMap<u16, PatternSequence>,
+    signals: HashMap<PinIndex, String>,
+    cells: HashMap<(u16, u32), String>,
+    chains: HashMap<u16, rust_stdf::CDR>,
+    structures: HashMap<u16, String>,
+}
+
+impl ScanMap {
+    pub(crate) fn add_psr(&mut self, psr: rust_stdf::PSR) {
+        let sequence = self.sequences.entry(psr.psr_indx).or_default();
+        sequence.name = psr.psr_nam;
+        for ((bgn, end), file) in psr.pat_bgn.iter().zip(&psr.pat_end).zip(psr.pat_file) {
+            sequence.patterns.push((*bgn, *end, file));
+        }
+    }
+
+    pub(crate) fn add_nmr(&mut self, nmr: rust_stdf::NMR) {
+        for (index, name) in nmr.pmr_indx.iter().zip(nmr.atpg_nam) {
+            self.signals.insert(*index, name);
+        }
+    }
+
+    pub(crate) fn add_cnr(&mut self, cnr: rust_stdf::CNR) {
+        self.cells.insert((cnr.chn_num, cnr.bit_pos), cnr.cell_nam);
+    }
+
+    pub(crate) fn add_ssr(&mut self, ssr: rust_stdf::SSR) {
+        for chain in ssr.chn_list {
+            self.structures.insert(chain, ssr.ssr_nam.clone());
+        }
+    }
+
+    // continuation records list further cells of the chain
+    pub(crate) fn add_cdr(&mut self, cdr: rust_stdf::CDR) {
+        match self.chains.get_mut(&cdr.cdr_indx) {
+            Some(chain) => chain.cell_lst.extend(cdr.cell_lst),
+            None => {
+                self.chains.insert(cdr.cdr_indx, cdr);
+            }
+        }
+    }
+
+    // the pattern file by its number in the sequence, otherwise
+    // by the cycle range the pattern was run over
+    fn pattern(&self, psr_ref: u16, pat_num: Option<u64>, cycle: Option<u64>) -> Option<String> {
+        let patterns = &self.sequences.get(&psr_ref)?.patterns;
+        let pattern = match (pat_num, cycle) {
+            (Some(pat_num), _) => patterns.get(pat_num as usize),
+            (None, Some(cycle)) => patterns
+                .iter()
+                .find(|(bgn, end, _)| (*bgn..=*end).contains(&cycle)),
+            (None, None) => None,
+        };
+        pattern.map(|(_, _, file)| file.clone())
+    }
+
+    // a cell by its CNR name, falling back to the cell list of the chain
+    fn cell(&self, chain: u16, bit_pos: u32) -> Option<String> {
+        self.cells.get(&(chain, bit_pos)).cloned().or_else(|| {
+            self.chains
+                .get(&chain)
+                .and_then(|x| x.cell_lst.get(bit_pos as usize))
+                .cloned()
+        })
+    }
+}
+
+// one row per fail logged by an STR, names are resolved when the
+// table is built as the naming records may follow the STRs
+#[derive(Default)]
+pub(crate) struct ScanFailRows {
+    part_index: Vec<usize>,
+    part_id: Vec<String>,
+    head_num: Vec<u32>,
+    site_num: Vec<u32>,
+    test_num: Vec<u32>,
+    test_txt: Vec<String>,
+    psr_ref: Vec<u16>,
+    pat_num: Vec<Option<u64>>,
+    cycle: Vec<Option<u64>>,
+    pin: Vec<Option<u64>>,
+    chain: Vec<Option<u64>>,
+    bit_pos: Vec<Option<u64>>,
+    expected: Vec<Option<u32>>,
+    captured: Vec<Option<u32>>,
+}
+
+impl ScanFailRows {
+    pub(crate) fn push(&mut self, part_index: usize, prr: &rust_stdf::PRR, rec: &rust_stdf::STR) {
+        // each array holds one entry per logged fail, or is empty when not logged
+        let cyc_ofst = uf_values(&rec.cyc_ofst);
+        let pmr_indx = uf_values(&rec.pmr_indx);
+        let chn_num = uf_values(&rec.chn_num);
+        let pat_num = uf_values(&rec.pat_num);
+        let bit_pos = uf_values(&rec.bit_pos);
+        let n_fails = [
+            cyc_ofst.len(),
+            pmr_indx.len(),
+            chn_num.len(),
+            pat_num.len(),
+            bit_pos.len(),
+            rec.exp_data.len(),
+            rec.cap_data.len(),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+
+        for i in 0..n_fails {
+            self.part_index.push(part_index);
+            self.part_id.push(prr.part_id.clone());
+            self.head_num.push(prr.head_num as u32);
+            self.site_num.push(prr.site_num as u32);
+            self.test_num.push(rec.test_num);
+            self.test_txt.push(rec.test_txt.clone());
+            self.psr_ref.push(rec.psr_ref);
+            self.pat_num.push(pat_num.get(i).copied());
+            self.cycle.push(cyc_ofst.get(i).map(|x| rec.cyc_base + x));
+            self.pin.push(pmr_indx.get(i).copied());
+            self.chain.push(chn_num.get(i).copied());
+            self.bit_pos
+                .push(bit_pos.get(i).map(|x| rec.bit_base as u64 + x));
+            self.expected.push(rec.exp_data.get(i).map(|x| *x as u32));
+            self.captured.push(rec.cap_data.get(i).map(|x| *x as u32));
+        }
+    }
+
+    pub(crate) fn into_df(
+        self,
+        file_name: &str,
+        retests: &[(usize, u32)],
+        keep: &[bool],
+        scan_map: &ScanMap,
+        pin_map: Option<&PinMap>,
+    ) -> PolarsResult<DataFrame> {
+        let mask: BooleanChunked = self.part_index.iter().map(|i| keep[*i]).collect();
+        let retest_index: Vec<u32> = self.part_index.iter().map(|i| retests[*i].1).collect();
+
+        let sequences: Vec<Option<String>> = self
+            .psr_ref
+            .iter()
+            .map(|x| scan_map.sequences.get(x).map(|x| x.name.clone()))
+            .collect();
+        let patterns: Vec<Option<String>> = (0..self.psr_ref.len())
+            .map(|i| scan_map.pattern(self.psr_ref[i], self.pat_num[i], self.cycle[i]))
+            .collect();
+        // pins by their NMR ATPG signal name, falling back to the pin map
+        let pins: Vec<Option<String>> = self
+            .pin
+            .iter()
+            .map(|x| {
+                x.map(|x| {
+                    let index = x as PinIndex;
+                    scan_map
+                        .signals
+                        .get(&index)
+                        .cloned()
+                        .unwrap_or_else(|| pin_name(pin_map, index))
+                })
+            })
+            .collect();
+        let chains: Vec<Option<String>> = self
+            .chain
+            .iter()
+            .map(|x| x.and_then(|x| scan_map.chains.get(&(x as u16)).map(|x| x.chn_nam.clone())))
+            .collect();
+        let structures: Vec<Option<String>> = self
+            .chain
+            .iter()
+            .map(|x| x.and_then(|x| scan_map.structures.get(&(x as u16)).cloned()))
+            .collect();
+        let cells: Vec<Option<String>> = self
+            .chain
+            .iter()
+            .zip(&self.bit_pos)
+            .map(|(chain, bit_pos)| scan_map.cell((*chain)? as u16, (*bit_pos)? as u32))
+            .collect();
+
+        DataFrame::new(vec![
+            Series::new("File Name", vec![file_name; self.part_id.len()]),
+            Series::new("Part ID", self.part_id),
+            Series::new("Retest Index", retest_index),
+            Series::new("Head", self.head_num),
+            Series::new("Site", self.site_num),
+            Series::new("Test Num", self.test_num),
+            Series::new("Test Name", self.test_txt),
+            Series::new("Pattern Sequence", sequences),
+            Series::new("Pattern Num", self.pat_num),
+            Series::new("Pattern File", patterns),
+            Series::new("Cycle", self.cycle),
+            Series::new("Pin", pins),
+            Series::new("Scan Structure", structures),
+            Series::new("Chain Num", self.chain),
+            Series::new("Chain Name", chains),
+            Series::new("Bit Position", self.bit_pos),
+            Series::new("Cell Name", cells),
+            Series::new("Expected", self.expected),
+            Series::new("Captured", self.captured),
+        ])?
+        .filter(&mask)
+    }
+}
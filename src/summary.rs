@@ -0,0 +1,691 @@
+use crate::limits::TestHeader;
+use crate::{BinNum, HeadNum, SiteNum};
+use chrono::{TimeZone, Utc};
+use polars::prelude::*;
+
+// HBR and SBR records share this layout
+#[derive(Debug, Clone)]
+pub(crate) struct BinRecord {
+    pub(crate) bin_type: &'static str,
+    pub(crate) head_num: HeadNum,
+    pub(crate) site_num: SiteNum,
+    pub(crate) bin_num: BinNum,
+    pub(crate) bin_cnt: u32,
+    pub(crate) bin_pf: char,
+    pub(crate) bin_nam: String,
+}
+
+// one row of the bin summary report
+#[derive(Debug, Clone)]
+pub(crate) struct BinRow {
+    pub(crate) lot_id: String,
+    pub(crate) bin_type: &'static str,
+    pub(crate) bin_num: BinNum,
+    pub(crate) bin_nam: String,
+    pub(crate) bin_pf: char,
+    pub(crate) head_num: HeadNum,
+    pub(crate) site_num: SiteNum,
+    pub(crate) summary_cnt: Option<u32>,
+    pub(crate) prr_cnt: u32,
+}
+
+pub(crate) fn bin_summary_df(rows: &[BinRow], file_name: Option<&str>) -> PolarsResult<DataFrame> {
+    let mut fields = vec![];
+
+    if let Some(file_name) = file_name {
+        fields.push(Series::new("File Name", vec![file_name; rows.len()]));
+    }
+
+    fields.append(&mut vec![
+        Series::new(
+            "Lot ID",
+            rows.iter().map(|x| x.lot_id.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Bin Type",
+            rows.iter().map(|x| x.bin_type).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Bin Num",
+            rows.iter().map(|x| x.bin_num as u32).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Bin Name",
+            rows.iter().map(|x| x.bin_nam.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Pass/Fail",
+            rows.iter()
+                .map(|x| x.bin_pf.to_string())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Head Num",
+            rows.iter().map(|x| x.head_num as u32).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Site Num",
+            rows.iter().map(|x| x.site_num as u32).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Summary Count",
+            rows.iter().map(|x| x.summary_cnt).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "PRR Count",
+            rows.iter().map(|x| x.prr_cnt).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Count Mismatch",
+            rows.iter()
+                .map(|x| x.summary_cnt != Some(x.prr_cnt))
+                .collect::<Vec<_>>(),
+        ),
+    ]);
+
+    DataFrame::new(fields)
+}
+
+// part counts behind one row of the yield report
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct YieldCounts {
+    pub(crate) tested: u32,      // every PRR, including retests
+    pub(crate) parts: u32,       // final test of each part
+    pub(crate) good: u32,        // final test of each part passed
+    pub(crate) first_parts: u32, // first test of each part
+    pub(crate) first_good: u32,  // first test of each part passed
+}
+
+// lot, file, head and site of a yield report row, `None` for the
+// levels summarised over
+pub(crate) type YieldKey = (String, Option<String>, Option<HeadNum>, Option<SiteNum>);
+
+pub(crate) fn yield_df(counts: &[(YieldKey, YieldCounts)]) -> PolarsResult<DataFrame> {
+    let ratio = |a: u32, b: u32| Some(a as f64 / b as f64).filter(|_| b > 0);
+
+    DataFrame::new(vec![
+        Series::new(
+            "Lot ID",
+            counts.iter().map(|(k, _)| k.0.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "File Name",
+            counts.iter().map(|(k, _)| k.1.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Head Num",
+            counts
+                .iter()
+                .map(|(k, _)| k.2.map(|x| x as u32))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Site Num",
+            counts
+                .iter()
+                .map(|(k, _)| k.3.map(|x| x as u32))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Total Parts",
+            counts.iter().map(|(_, c)| c.parts).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Good Parts",
+            counts.iter().map(|(_, c)| c.good).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Bad Parts",
+            counts
+                .iter()
+                .map(|(_, c)| c.parts - c.good)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Retests",
+            counts
+                .iter()
+                .map(|(_, c)| c.tested - c.first_parts)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "First Pass Yield",
+            counts
+                .iter()
+                .map(|(_, c)| ratio(c.first_good, c.first_parts))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Final Yield",
+            counts
+                .iter()
+                .map(|(_, c)| ratio(c.good, c.parts))
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+// summary statistics of the results of one test
+#[derive(Debug, Clone)]
+pub(crate) struct TestStats {
+    pub(crate) count: u32,
+    pub(crate) mean: Option<f64>,
+    pub(crate) std_dev: Option<f64>,
+    pub(crate) min: Option<f64>,
+    pub(crate) p5: Option<f64>,
+    pub(crate) p25: Option<f64>,
+    pub(crate) median: Option<f64>,
+    pub(crate) p75: Option<f64>,
+    pub(crate) p95: Option<f64>,
+    pub(crate) max: Option<f64>,
+    pub(crate) cp: Option<f64>,
+    pub(crate) cpk: Option<f64>,
+    pub(crate) fail_count: u32,
+}
+
+pub(crate) fn test_stats(values: &mut [f64], header: &TestHeader, fail_count: u32) -> TestStats {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let n = values.len();
+
+    // linear interpolation between the closest ranks
+    let percentile = |p: f64| {
+        let rank = p * (n.max(1) - 1) as f64;
+        let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+        Some(values.get(lo)? + (values.get(hi)? - values.get(lo)?) * (rank - lo as f64))
+    };
+
+    let mean = Some(values.iter().sum::<f64>() / n as f64).filter(|_| n > 0);
+    // sample standard deviation
+    let std_dev = mean.filter(|_| n > 1).map(|mean| {
+        let sum_sq: f64 = values.iter().map(|x| (x - mean).powi(2)).sum();
+        (sum_sq / (n - 1) as f64).sqrt()
+    });
+
+    // Cp needs both limits, Cpk uses whichever limits are present
+    let sigma = std_dev.filter(|x| *x > 0.0);
+    let lo_limit = header.lo_limit.map(f64::from);
+    let hi_limit = header.hi_limit.map(f64::from);
+    let cp = match (lo_limit, hi_limit, sigma) {
+        (Some(lo), Some(hi), Some(sigma)) => Some((hi - lo) / (6.0 * sigma)),
+        _ => None,
+    };
+    let cpl = match (lo_limit, mean, sigma) {
+        (Some(lo), Some(mean), Some(sigma)) => Some((mean - lo) / (3.0 * sigma)),
+        _ => None,
+    };
+    let cpu = match (hi_limit, mean, sigma) {
+        (Some(hi), Some(mean), Some(sigma)) => Some((hi - mean) / (3.0 * sigma)),
+        _ => None,
+    };
+    let cpk = match (cpl, cpu) {
+        (Some(cpl), Some(cpu)) => Some(cpl.min(cpu)),
+        (cpl, cpu) => cpl.or(cpu),
+    };
+
+    TestStats {
+        count: n as u32,
+        mean,
+        std_dev,
+        min: values.first().copied(),
+        p5: percentile(0.05),
+        p25: percentile(0.25),
+        median: percentile(0.5),
+        p75: percentile(0.75),
+        p95: percentile(0.95),
+        max: values.last().copied(),
+        cp,
+        cpk,
+        fail_count,
+    }
+}
+
+// head and site of a test statistics row, `None` when all sites are summarised
+pub(crate) type StatsRow = (Option<(HeadNum, SiteNum)>, TestHeader, TestStats);
+
+pub(crate) fn test_stats_df(
+    file_name: &str,
+    lot_id: &str,
+    rows: &[StatsRow],
+) -> PolarsResult<DataFrame> {
+    let stat = |name: &str, field: fn(&TestStats) -> Option<f64>| {
+        Series::new(
+            name,
+            rows.iter().map(|(_, _, x)| field(x)).collect::<Vec<_>>(),
+        )
+    };
+
+    DataFrame::new(vec![
+        Series::new("File Name", vec![file_name; rows.len()]),
+        Series::new("Lot ID", vec![lot_id; rows.len()]),
+        Series::new(
+            "Head Num",
+            rows.iter()
+                .map(|(x, _, _)| x.map(|x| x.0 as u32))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Site Num",
+            rows.iter()
+                .map(|(x, _, _)| x.map(|x| x.1 as u32))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Num",
+            rows.iter().map(|(_, h, _)| h.test_num).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Name",
+            rows.iter()
+                .map(|(_, h, _)| h.test_txt.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Units",
+            rows.iter()
+                .map(|(_, h, _)| h.units.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Lo Limit",
+            rows.iter().map(|(_, h, _)| h.lo_limit).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Hi Limit",
+            rows.iter().map(|(_, h, _)| h.hi_limit).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Count",
+            rows.iter().map(|(_, _, x)| x.count).collect::<Vec<_>>(),
+        ),
+        stat("Mean", |x| x.mean),
+        stat("Std Dev", |x| x.std_dev),
+        stat("Min", |x| x.min),
+        stat("P5", |x| x.p5),
+        stat("P25", |x| x.p25),
+        stat("Median", |x| x.median),
+        stat("P75", |x| x.p75),
+        stat("P95", |x| x.p95),
+        stat("Max", |x| x.max),
+        stat("Cp", |x| x.cp),
+        stat("Cpk", |x| x.cpk),
+        Series::new(
+            "Fail Count",
+            rows.iter()
+                .map(|(_, _, x)| x.fail_count)
+                .collect::<Vec<_>>(),
+        ),
+        stat("Fail Rate", |x| {
+            Some(x.fail_count as f64 / x.count as f64).filter(|_| x.count > 0)
+        }),
+    ])
+}
+
+// TSR values recomputed from the individual test results
+#[derive(Debug, Default, Clone)]
+pub(crate) struct TsrComputed {
+    pub(crate) exec_cnt: u32,
+    pub(crate) fail_cnt: u32,
+    pub(crate) test_min: Option<f64>,
+    pub(crate) test_max: Option<f64>,
+    pub(crate) tst_sums: Option<f64>,
+    pub(crate) tst_sqrs: Option<f64>,
+}
+
+// names of the TSR fields which disagree with the computed values,
+// skipping fields the tester flagged as invalid
+pub(crate) fn tsr_mismatches(tsr: &rust_stdf::TSR, computed: &TsrComputed) -> String {
+    // 4,294,967,295 indicates the count is missing
+    let count = |x: u32| Some(x).filter(|x| *x != u32::MAX);
+    // results are summed as f32 on the tester
+    let is_close = |a: f64, b: f64| (a - b).abs() <= 1e-4 * a.abs().max(b.abs());
+
+    // bit 0 set = TEST_MIN invalid
+    // bit 1 set = TEST_MAX invalid
+    // bit 4 set = TST_SUMS invalid
+    // bit 5 set = TST_SQRS invalid
+    let opt_flag = tsr.opt_flag[0];
+    let value = |x: f32, bit: u8| Some(x as f64).filter(|_| opt_flag & bit == 0);
+
+    let counts = [
+        ("EXEC_CNT", count(tsr.exec_cnt), computed.exec_cnt),
+        ("FAIL_CNT", count(tsr.fail_cnt), computed.fail_cnt),
+    ];
+    let values = [
+        (
+            "TEST_MIN",
+            value(tsr.test_min, 0b0000_0001),
+            computed.test_min,
+        ),
+        (
+            "TEST_MAX",
+            value(tsr.test_max, 0b0000_0010),
+            computed.test_max,
+        ),
+        (
+            "TST_SUMS",
+            value(tsr.tst_sums, 0b0001_0000),
+            computed.tst_sums,
+        ),
+        (
+            "TST_SQRS",
+            value(tsr.tst_sqrs, 0b0010_0000),
+            computed.tst_sqrs,
+        ),
+    ];
+
+    counts
+        .iter()
+        .filter(|(_, tester, computed)| tester.is_some_and(|x| x != *computed))
+        .map(|(name, _, _)| *name)
+        .chain(
+            values
+                .iter()
+                .filter(|(_, tester, computed)| match (tester, computed) {
+                    (Some(a), Some(b)) => !is_close(*a, *b),
+                    (Some(_), None) => true,
+                    _ => false,
+                })
+                .map(|(name, _, _)| *name),
+        )
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+pub(crate) fn test_summary_df(
+    file_name: &str,
+    lot_id: &str,
+    rows: &[(rust_stdf::TSR, TsrComputed, String)],
+) -> PolarsResult<DataFrame> {
+    let count = |x: u32| Some(x).filter(|x| *x != u32::MAX);
+    let tester = |name: &str, field: fn(&rust_stdf::TSR) -> f32, bit: u8| {
+        Series::new(
+            name,
+            rows.iter()
+                .map(|(x, _, _)| Some(field(x)).filter(|_| x.opt_flag[0] & bit == 0))
+                .collect::<Vec<_>>(),
+        )
+    };
+    let computed = |name: &str, field: fn(&TsrComputed) -> Option<f64>| {
+        Series::new(
+            name,
+            rows.iter().map(|(_, x, _)| field(x)).collect::<Vec<_>>(),
+        )
+    };
+
+    DataFrame::new(vec![
+        Series::new("File Name", vec![file_name; rows.len()]),
+        Series::new("Lot ID", vec![lot_id; rows.len()]),
+        Series::new(
+            "Head Num",
+            rows.iter()
+                .map(|(x, _, _)| x.head_num as u32)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Site Num",
+            rows.iter()
+                .map(|(x, _, _)| x.site_num as u32)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Type",
+            rows.iter()
+                .map(|(x, _, _)| x.test_typ.to_string())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Num",
+            rows.iter().map(|(x, _, _)| x.test_num).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Name",
+            rows.iter()
+                .map(|(x, _, _)| x.test_nam.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Sequencer Name",
+            rows.iter()
+                .map(|(x, _, _)| x.seq_name.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Label",
+            rows.iter()
+                .map(|(x, _, _)| x.test_lbl.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Exec Count",
+            rows.iter()
+                .map(|(x, _, _)| count(x.exec_cnt))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Fail Count",
+            rows.iter()
+                .map(|(x, _, _)| count(x.fail_cnt))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Alarm Count",
+            rows.iter()
+                .map(|(x, _, _)| count(x.alrm_cnt))
+                .collect::<Vec<_>>(),
+        ),
+        tester("Test Time", |x| x.test_tim, 0b0000_0100),
+        tester("Test Min", |x| x.test_min, 0b0000_0001),
+        tester("Test Max", |x| x.test_max, 0b0000_0010),
+        tester("Test Sums", |x| x.tst_sums, 0b0001_0000),
+        tester("Test Squares", |x| x.tst_sqrs, 0b0010_0000),
+        Series::new(
+            "Computed Exec Count",
+            rows.iter().map(|(_, x, _)| x.exec_cnt).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Computed Fail Count",
+            rows.iter().map(|(_, x, _)| x.fail_cnt).collect::<Vec<_>>(),
+        ),
+        computed("Computed Min", |x| x.test_min),
+        computed("Computed Max", |x| x.test_max),
+        computed("Computed Sums", |x| x.tst_sums),
+        computed("Computed Squares", |x| x.tst_sqrs),
+        Series::new(
+            "Mismatches",
+            rows.iter().map(|(_, _, x)| x.clone()).collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+// limits in force for a run of consecutive parts, from the first to the last part
+pub(crate) struct LimitRange {
+    pub(crate) site: Option<(HeadNum, SiteNum)>,
+    pub(crate) header: TestHeader,
+    pub(crate) first_part_id: String,
+    pub(crate) last_part_id: String,
+    pub(crate) n_parts: u32,
+}
+
+pub(crate) fn limit_changes_df(
+    file_name: &str,
+    lot_id: &str,
+    rows: &[LimitRange],
+) -> PolarsResult<DataFrame> {
+    DataFrame::new(vec![
+        Series::new("File Name", vec![file_name; rows.len()]),
+        Series::new("Lot ID", vec![lot_id; rows.len()]),
+        Series::new(
+            "Head Num",
+            rows.iter()
+                .map(|x| x.site.map(|x| x.0 as u32))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Site Num",
+            rows.iter()
+                .map(|x| x.site.map(|x| x.1 as u32))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Num",
+            rows.iter().map(|x| x.header.test_num).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Test Name",
+            rows.iter()
+                .map(|x| x.header.test_txt.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Units",
+            rows.iter()
+                .map(|x| x.header.units.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Lo Limit",
+            rows.iter().map(|x| x.header.lo_limit).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Hi Limit",
+            rows.iter().map(|x| x.header.hi_limit).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "First Part ID",
+            rows.iter()
+                .map(|x| x.first_part_id.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Last Part ID",
+            rows.iter()
+                .map(|x| x.last_part_id.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new("Parts", rows.iter().map(|x| x.n_parts).collect::<Vec<_>>()),
+    ])
+}
+
+// per-wafer summary from the WRR counts, with the PRRs tested on each wafer
+pub(crate) fn wafer_summary_df(
+    file_name: &str,
+    lot_id: &str,
+    wafers: &[(Option<rust_stdf::WIR>, rust_stdf::WRR)],
+    part_wafer_ids: &[Option<String>],
+) -> PolarsResult<DataFrame> {
+    // 4,294,967,295 indicates the count is missing
+    let count = |x: u32| Some(x).filter(|x| *x != u32::MAX);
+    let timestamp = |t: u32| Utc.timestamp_opt(t.into(), 0).unwrap().to_rfc3339();
+
+    DataFrame::new(vec![
+        Series::new("File Name", vec![file_name; wafers.len()]),
+        Series::new("Lot ID", vec![lot_id; wafers.len()]),
+        Series::new(
+            "Head Num",
+            wafers
+                .iter()
+                .map(|(_, wrr)| wrr.head_num as u32)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Wafer ID",
+            wafers
+                .iter()
+                .map(|(_, wrr)| wrr.wafer_id.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Fab Wafer ID",
+            wafers
+                .iter()
+                .map(|(_, wrr)| wrr.fabwf_id.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Frame ID",
+            wafers
+                .iter()
+                .map(|(_, wrr)| wrr.frame_id.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Mask ID",
+            wafers
+                .iter()
+                .map(|(_, wrr)| wrr.mask_id.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Start Time",
+            wafers
+                .iter()
+                .map(|(wir, _)| wir.as_ref().map(|wir| timestamp(wir.start_t)))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Finish Time",
+            wafers
+                .iter()
+                .map(|(_, wrr)| timestamp(wrr.finish_t))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Part Count",
+            wafers
+                .iter()
+                .map(|(_, wrr)| wrr.part_cnt)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Retest Count",
+            wafers
+                .iter()
+                .map(|(_, wrr)| count(wrr.rtst_cnt))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Abort Count",
+            wafers
+                .iter()
+                .map(|(_, wrr)| count(wrr.abrt_cnt))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Good Count",
+            wafers
+                .iter()
+                .map(|(_, wrr)| count(wrr.good_cnt))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Functional Count",
+            wafers
+                .iter()
+                .map(|(_, wrr)| count(wrr.func_cnt))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "PRR Count",
+            wafers
+                .iter()
+                .map(|(_, wrr)| {
+                    part_wafer_ids
+                        .iter()
+                        .filter(|x| x.as_ref() == Some(&wrr.wafer_id))
+                        .count() as u32
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "Yield",
+            wafers
+                .iter()
+                .map(|(_, wrr)| {
+                    count(wrr.good_cnt)
+                        .filter(|_| wrr.part_cnt > 0)
+                        .map(|good| good as f64 / wrr.part_cnt as f64)
+                })
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}